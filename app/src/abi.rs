@@ -0,0 +1,53 @@
+use ethers_core::abi::{AbiParser, Function, ParamType, Token};
+
+/// Error(string), emitted by `require` and `revert("...")`
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Panic(uint256), emitted by failed asserts, overflow, division by zero...
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// One contract function, used to build calldata and decode what it returns.
+pub struct ContractFn(Function);
+
+impl ContractFn {
+    /// Accepts a human-readable signature
+    /// (`"function balanceOf(address) view returns (uint256)"`)
+    /// or a JSON ABI entry for the function.
+    pub fn parse(abi: &str) -> Result<Self, String> {
+        let abi = abi.trim();
+        let function = if abi.starts_with('{') {
+            serde_json::from_str(abi).map_err(|e| format!("Bad ABI entry: {}", e))?
+        } else {
+            AbiParser::default().parse_function(abi).map_err(|e| format!("Bad signature '{}': {}", abi, e))?
+        };
+        Ok(Self(function))
+    }
+
+    /// Selector followed by the ABI-encoded `args`, which must match the inputs.
+    pub fn encode(&self, args: &[Token]) -> Result<Vec<u8>, String> {
+        self.0.encode_input(args).map_err(|e| format!("{}: {}", self.0.signature(), e))
+    }
+
+    /// Decodes return data into one token per output.
+    pub fn decode(&self, output: &[u8]) -> Result<Vec<Token>, String> {
+        if output.is_empty() && !self.0.outputs.is_empty() {
+            return Err(format!("{} returned nothing (no contract at this address?)", self.0.signature()));
+        }
+        self.0.decode_output(output).map_err(|e| format!("{}: {}", self.0.signature(), e))
+    }
+}
+
+/// Human-readable reason of standard revert data, if it is one.
+pub fn decode_revert(data: &[u8]) -> Option<String> {
+    let (selector, payload) = (data.get(..4)?, &data[4..]);
+    if selector == ERROR_SELECTOR {
+        match ethers_core::abi::decode(&[ParamType::String], payload).ok()?.pop()? {
+            Token::String(reason) => Some(reason),
+            _ => None,
+        }
+    } else if selector == PANIC_SELECTOR {
+        let code = ethers_core::abi::decode(&[ParamType::Uint(256)], payload).ok()?.pop()?.into_uint()?;
+        Some(format!("panic 0x{:02x}", code.low_u64()))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,291 @@
+use std::cmp::Ordering;
+
+use ethers_core::types::U256;
+
+/// Decimals of ETH (wei per ether = 10^18).
+pub const ETHER_DECIMALS: u8 = 18;
+/// 10^78 no longer fits in a U256.
+const MAX_DECIMALS: u8 = 77;
+
+/// An exact token amount: `raw / 10^decimals`.
+/// Arithmetic only combines amounts of the same token (same decimals).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Amount {
+    raw: U256,
+    decimals: u8,
+}
+
+impl Amount {
+    /// `raw` base units of a token with `decimals` (e.g. from `decimals()`).
+    pub fn new(raw: U256, decimals: u8) -> Result<Self, String> {
+        if decimals > MAX_DECIMALS {
+            return Err(format!("Unsupported token decimals: {}", decimals));
+        }
+        Ok(Self { raw, decimals })
+    }
+
+    pub fn wei(raw: impl Into<U256>) -> Self {
+        Self { raw: raw.into(), decimals: ETHER_DECIMALS }
+    }
+
+    pub fn raw(&self) -> U256 {
+        self.raw
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.raw.is_zero()
+    }
+
+    /// Parses a decimal string like "1.5" or ".05" exactly. Rejects more
+    /// fractional digits than the token has instead of rounding them away.
+    pub fn parse(text: &str, decimals: u8) -> Result<Self, String> {
+        let text = text.trim();
+        let (int, frac) = text.split_once('.').unwrap_or((text, ""));
+        if int.is_empty() && frac.is_empty() {
+            return Err("Enter an amount".to_string());
+        }
+        if !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(format!("Not a number: {}", text));
+        }
+        if frac.len() > decimals as usize {
+            return Err(format!("At most {} decimal places", decimals));
+        }
+        let digits = format!("{}{:0<width$}", int, frac, width = decimals as usize);
+        let raw = U256::from_dec_str(&digits).map_err(|_| "Amount too large".to_string())?;
+        Self::new(raw, decimals)
+    }
+
+    pub fn parse_ether(text: &str) -> Result<Self, String> {
+        Self::parse(text, ETHER_DECIMALS)
+    }
+
+    fn unit(&self) -> U256 {
+        U256::exp10(self.decimals as usize)
+    }
+
+    fn same_token(&self, other: &Amount) -> Option<()> {
+        (self.decimals == other.decimals).then_some(())
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.same_token(&other)?;
+        Some(Self { raw: self.raw.checked_add(other.raw)?, ..self })
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.same_token(&other)?;
+        Some(Self { raw: self.raw.checked_sub(other.raw)?, ..self })
+    }
+
+    /// Multiplies by a plain count, e.g. a gas price by a gas limit.
+    pub fn checked_mul(self, n: impl Into<U256>) -> Option<Amount> {
+        Some(Self { raw: self.raw.checked_mul(n.into())?, ..self })
+    }
+
+    /// Scales by `num / den`, rounding down (e.g. 110 / 100 for a 10% bump).
+    pub fn mul_div(self, num: u64, den: u64) -> Option<Amount> {
+        if den == 0 {
+            return None;
+        }
+        Some(Self { raw: self.raw.checked_mul(num.into())? / den, ..self })
+    }
+
+    /// Integer and fractional digits after rounding half up to `digits` places.
+    fn rounded_parts(&self, digits: u8) -> (U256, String) {
+        let unit = self.unit();
+        if digits >= self.decimals {
+            let frac = if self.decimals == 0 {
+                String::new()
+            } else {
+                format!("{:0>width$}", (self.raw % unit).to_string(), width = self.decimals as usize)
+            };
+            return (self.raw / unit, format!("{:0<width$}", frac, width = digits as usize));
+        }
+        let step = U256::exp10((self.decimals - digits) as usize);
+        let mut scaled = self.raw / step;
+        let rem = self.raw % step;
+        // rem * 2 >= step, without overflowing at 77 decimals
+        if rem >= step - rem {
+            scaled = scaled.saturating_add(U256::one());
+        }
+        let frac_unit = U256::exp10(digits as usize);
+        let frac = if digits == 0 {
+            String::new()
+        } else {
+            format!("{:0>width$}", (scaled % frac_unit).to_string(), width = digits as usize)
+        };
+        (scaled / frac_unit, frac)
+    }
+
+    /// Exactly `digits` fractional places, rounded half up: "0.0050".
+    pub fn format(&self, digits: u8) -> String {
+        let (int, frac) = self.rounded_parts(digits);
+        if frac.is_empty() { int.to_string() } else { format!("{}.{}", int, frac) }
+    }
+
+    /// Like `format`, with the browser locale's grouping and decimal separators.
+    pub fn format_locale(&self, digits: u8) -> String {
+        let (int, frac) = self.rounded_parts(digits);
+        let int = int.to_string();
+        SEPARATORS.with(|sep| {
+            let mut grouped = String::with_capacity(int.len() * 4 / 3);
+            for (i, c) in int.chars().enumerate() {
+                if i > 0 && (int.len() - i) % 3 == 0 {
+                    grouped.push_str(&sep.group);
+                }
+                grouped.push(c);
+            }
+            if frac.is_empty() { grouped } else { format!("{}{}{}", grouped, sep.decimal, frac) }
+        })
+    }
+}
+
+/// Exact value with trailing zeros trimmed: "1.5", "0.000000000000000001".
+impl std::fmt::Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = self.format(self.decimals);
+        let s = if s.contains('.') { s.trim_end_matches('0').trim_end_matches('.') } else { &s };
+        f.write_str(s)
+    }
+}
+
+/// Amounts of different tokens are not comparable.
+impl PartialOrd for Amount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.same_token(other)?;
+        Some(self.raw.cmp(&other.raw))
+    }
+}
+
+struct Separators {
+    group: String,
+    decimal: String,
+}
+
+thread_local! {
+    // Detected on first use
+    static SEPARATORS: Separators = detect_separators();
+}
+
+// Asks Intl.NumberFormat how the user's locale writes 1234.5
+fn detect_separators() -> Separators {
+    let mut sep = Separators { group: ",".to_string(), decimal: ".".to_string() };
+    // js-sys calls panic outside the browser
+    if cfg!(not(target_arch = "wasm32")) {
+        return sep;
+    }
+    let format = js_sys::Intl::NumberFormat::new(&js_sys::Array::new(), &js_sys::Object::new());
+    for part in format.format_to_parts(1234.5).iter() {
+        let get = |key: &str| js_sys::Reflect::get(&part, &key.into()).ok().and_then(|v| v.as_string());
+        match (get("type").as_deref(), get("value")) {
+            (Some("group"), Some(v)) => sep.group = v,
+            (Some("decimal"), Some(v)) => sep.decimal = v,
+            _ => {}
+        }
+    }
+    sep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eth(text: &str) -> Amount {
+        Amount::parse_ether(text).unwrap()
+    }
+
+    #[test]
+    fn parses_exactly() {
+        assert_eq!(eth("1.5").raw(), U256::from(1_500_000_000_000_000_000u64));
+        assert_eq!(eth(".05").raw(), U256::from(50_000_000_000_000_000u64));
+        assert_eq!(eth(" 2 ").raw(), U256::exp10(18) * 2);
+        assert_eq!(eth("0.000000000000000001").raw(), U256::one());
+        assert_eq!(Amount::parse("12.34", 2).unwrap().raw(), U256::from(1234));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        assert_eq!(Amount::parse("1.005", 2).unwrap_err(), "At most 2 decimal places");
+        assert_eq!(Amount::parse_ether("0.0000000000000000001").unwrap_err(), "At most 18 decimal places");
+        assert_eq!(Amount::parse_ether("-1").unwrap_err(), "Not a number: -1");
+        assert_eq!(Amount::parse_ether("1e18").unwrap_err(), "Not a number: 1e18");
+        assert_eq!(Amount::parse_ether("").unwrap_err(), "Enter an amount");
+        assert_eq!(Amount::parse_ether(".").unwrap_err(), "Enter an amount");
+        assert!(Amount::parse_ether("1.2.3").is_err());
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        // U256::MAX is about 1.16e77
+        let max = U256::MAX.to_string();
+        assert_eq!(Amount::parse(&max, 0).unwrap().raw(), U256::MAX);
+        assert_eq!(Amount::parse(&format!("{}0", max), 0).unwrap_err(), "Amount too large");
+        assert_eq!(Amount::parse_ether(&max).unwrap_err(), "Amount too large");
+        assert!(Amount::new(U256::one(), 78).is_err());
+    }
+
+    #[test]
+    fn formats_rounding_half_up() {
+        assert_eq!(eth("0.00495").format(4), "0.0050");
+        assert_eq!(eth("0.00494999").format(4), "0.0049");
+        assert_eq!(eth("1.5").format(0), "2");
+        assert_eq!(eth("1.4999").format(0), "1");
+        // Carry into the integer part
+        assert_eq!(eth("9.99995").format(4), "10.0000");
+        assert_eq!(eth("0.99999").format(2), "1.00");
+        // More places than the token has pads with zeros
+        assert_eq!(Amount::parse("1.5", 2).unwrap().format(4), "1.5000");
+    }
+
+    #[test]
+    fn display_trims_trailing_zeros() {
+        assert_eq!(eth("1.500").to_string(), "1.5");
+        assert_eq!(eth("2").to_string(), "2");
+        assert_eq!(eth("0").to_string(), "0");
+        assert_eq!(eth("10").to_string(), "10");
+        assert_eq!(Amount::wei(1u64).to_string(), "0.000000000000000001");
+    }
+
+    #[test]
+    fn arithmetic_refuses_to_mix_tokens() {
+        let a = eth("1.5");
+        let b = eth("0.5");
+        assert_eq!(a.checked_add(b), Some(eth("2")));
+        assert_eq!(a.checked_sub(b), Some(eth("1")));
+        assert_eq!(b.checked_sub(a), None);
+        assert_eq!(Amount::wei(U256::MAX).checked_add(Amount::wei(1u64)), None);
+        assert!(a > b);
+
+        let usdc = Amount::parse("1.5", 6).unwrap();
+        assert_eq!(a.checked_add(usdc), None);
+        assert_eq!(a.checked_sub(usdc), None);
+        assert_eq!(a.partial_cmp(&usdc), None);
+        assert_ne!(a, usdc);
+    }
+
+    #[test]
+    fn rounds_at_the_decimal_extremes() {
+        let whole = Amount::new(U256::from(5), 0).unwrap();
+        assert_eq!(whole.format(0), "5");
+        assert_eq!(whole.format(2), "5.00");
+        assert_eq!(whole.to_string(), "5");
+
+        // U256::MAX at 77 decimals is 1.157920892...; rounding must not overflow
+        let max = Amount::new(U256::MAX, 77).unwrap();
+        assert_eq!(max.format(2), "1.16");
+        assert_eq!(max.format(0), "1");
+        assert_eq!(max.format(77), format!("1.{}", &U256::MAX.to_string()[1..]));
+        let tiny = Amount::new(U256::one(), 77).unwrap();
+        assert_eq!(tiny.format(4), "0.0000");
+        assert_eq!(tiny.to_string(), format!("0.{}1", "0".repeat(76)));
+    }
+
+    #[test]
+    fn groups_with_the_native_separators() {
+        assert_eq!(eth("1234567.891").format_locale(2), "1,234,567.89");
+        assert_eq!(eth("999.999").format_locale(2), "1,000.00");
+        assert_eq!(eth("123").format_locale(0), "123");
+        assert_eq!(eth("1000").format_locale(0), "1,000");
+        assert_eq!(eth("0.5").format_locale(1), "0.5");
+    }
+}
@@ -2,6 +2,7 @@ use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
 
+use crate::history::HistoryView;
 use crate::wallet::WalletView;
 
 #[component]
@@ -15,6 +16,7 @@ pub fn App() -> impl IntoView {
             <main class="container">
                 <Routes>
                     <Route path="" view=WalletView/>
+                    <Route path="/history" view=HistoryView/>
                 </Routes>
             </main>
         </Router>
@@ -3,10 +3,11 @@
 #[tokio::main]
 pub async fn main() {
     use ethers_core::types::U256;
-    use ethers_core::abi::{encode, Token};
+    use ethers_core::abi::Token;
     use ethers_core::utils::keccak256;
-    use serde_json::json; 
-    use reqwest::Client;
+    use serde_json::json;
+    use diamond_wallet_app::abi::ContractFn;
+    use diamond_wallet_app::rpc::{call_contract, eth_call, BlockTag, Network};
 
     println!("Starting Debug...");
     
@@ -18,49 +19,29 @@ pub async fn main() {
 
     // 1. Check Implementation Code
     println!("Checking Implementation Code...");
-    {
-        let client = Client::new();
-        let res = client.post("https://sepolia.base.org")
-            .json(&json!({
-                "jsonrpc": "2.0",
-                "method": "eth_getCode",
-                "params": [implementation_addr, "latest"],
-                "id": 1
-            }))
-            .send().await.expect("RPC failed");
-        let text = res.text().await.expect("Text failed");
-        println!("Impl Code Check: {}", text);
+    match eth_call(Network::BaseSepolia, "eth_getCode", json!([implementation_addr, "latest"])).await {
+        Ok(code) => println!("Impl Code Check: {}", code),
+        Err(e) => println!("Impl Code Check failed: {}", e),
     }
 
     // 2. Simulate createAccount with Random Salt
     println!("Simulating createAccount (Random Salt)...");
     let random_salt = keccak256("random_salt_999");
-    let selector = hex::decode("025e3789").unwrap();
-    let mut full_data = selector;
-    full_data.extend(encode(&[
+    let create_fn = ContractFn::parse(
+        "function createAccount(address implementation, bytes32 salt, uint256 chainId, address tokenContract, uint256 tokenId) returns (address)"
+    ).unwrap();
+    let args = [
          Token::Address(implementation_addr.parse().unwrap()),
          Token::FixedBytes(random_salt.to_vec()),
          Token::Uint(U256::from(chain_id)),
          Token::Address(nft_addr.parse().unwrap()),
          Token::Uint(U256::from(token_id))
-    ]));
-    let full_data_hex = format!("0x{}", hex::encode(full_data));
+    ];
 
-    {
-        let client = Client::new();
-        let res = client.post("https://sepolia.base.org")
-            .json(&json!({
-                "jsonrpc": "2.0",
-                "method": "eth_call",
-                "params": [{
-                    "to": registry_addr,
-                    "data": full_data_hex
-                }, "latest"],
-                "id": 1
-            }))
-            .send().await.expect("RPC failed");
-        let text = res.text().await.expect("Text failed");
-        println!("createAccount Result: {}", text);
+    match call_contract(Network::BaseSepolia, registry_addr, &create_fn, &args, BlockTag::Latest).await {
+        Ok(tokens) => println!("createAccount Result: {:?}", tokens),
+        // Reverts carry their decoded reason
+        Err(e) => println!("createAccount Failed: {}", e),
     }
 }
 
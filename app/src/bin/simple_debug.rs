@@ -8,8 +8,7 @@ fn main() {
 
     println!("Starting Simple Debug...");
 
-    let registry_addr = "0x000000006551c19487814612e58FE06813775758";
-    let implementation_addr = "0xfb28ae9ffc69dd62718a780cb657a59c0b4e7aae8";
+    let implementation_addr = "0xfb28ae9ffc69dd62718a780cb657a59c0b4e7aae";
     let nft_addr = "0x66994e547cb9014191f50c7c7ee8cf5e80d3b89e"; 
     let chain_id = 84532u64;
     let token_id = 1u64;
@@ -20,7 +19,7 @@ fn main() {
     println!("Addresses parsed OK.");
 
     println!("2. Hex Decode...");
-    let sel = hex::decode("c6bdc908").expect("Selector decode failed");
+    let _sel = hex::decode("c6bdc908").expect("Selector decode failed");
     println!("Hex decoded OK.");
 
     println!("3. ABI Encode...");
@@ -33,13 +32,14 @@ fn main() {
     println!("Salt derived OK.");
 
     println!("4. Encode Final Payload...");
-    let call_data = encode(&[
+    let args = [
         Token::Address(impl_a),
         Token::FixedBytes(salt.to_vec()), 
         Token::Uint(U256::from(chain_id)),
         Token::Address(nft_a),
         Token::Uint(U256::from(token_id))
-    ]);
+    ];
+    let _call_data = encode(&args);
     println!("Payload encoded OK.");
 
     println!("ALL GOOD.");
@@ -0,0 +1,325 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use futures::channel::oneshot;
+use serde_json::Value;
+
+use crate::rpc::{Network, RpcError};
+use crate::storage::StorageBackend;
+
+pub const CACHE_KEY: &str = "diamond_wallet_rpc_cache";
+
+/// Blocks behind the head after which a block's data is treated as final.
+const FINALITY_DEPTH: u64 = 64;
+/// Permanent entries kept in memory and on disk; the least recently used go first.
+const MAX_PERMANENT: usize = 1000;
+
+/// How long a response stays valid.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Lifetime {
+    /// Cannot change (chain id, deployed code, finalized data)
+    Permanent,
+    /// Valid until the chain moves past the head it was read at
+    UntilNextHead,
+}
+
+struct Entry {
+    value: Value,
+    /// `None` for permanent entries
+    head: Option<u64>,
+    last_used: u64,
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CacheStats {
+    pub hits: u32,
+    pub misses: u32,
+    /// Requests answered by an identical one already in flight
+    pub joined: u32,
+    pub entries: usize,
+}
+
+type Waiter = oneshot::Sender<Result<Value, RpcError>>;
+
+thread_local! {
+    static ENTRIES: RefCell<HashMap<String, Entry>> = RefCell::new(HashMap::new());
+    static IN_FLIGHT: RefCell<HashMap<String, Vec<Waiter>>> = RefCell::new(HashMap::new());
+    /// Latest head seen per chain id
+    static HEADS: RefCell<HashMap<u64, u64>> = RefCell::new(HashMap::new());
+    static STATS: Cell<CacheStats> = Cell::new(CacheStats::default());
+    static CLOCK: Cell<u64> = const { Cell::new(0) };
+    /// Permanent entries changed since the last `persist`
+    static DIRTY: Cell<bool> = const { Cell::new(false) };
+}
+
+fn tick() -> u64 {
+    CLOCK.with(|c| {
+        c.set(c.get() + 1);
+        c.get()
+    })
+}
+
+fn count(f: impl FnOnce(&mut CacheStats)) {
+    STATS.with(|s| {
+        let mut stats = s.get();
+        f(&mut stats);
+        s.set(stats);
+    });
+}
+
+fn head(network: Network) -> Option<u64> {
+    HEADS.with(|h| h.borrow().get(&network.chain_id()).copied())
+}
+
+pub fn key(network: Network, method: &str, params: &Value) -> String {
+    format!("{}:{}:{}", network.chain_id(), method, params)
+}
+
+/// A block parameter older than `FINALITY_DEPTH`, i.e. one whose state is fixed.
+fn is_final(network: Network, block: &Value) -> bool {
+    let number = block.as_str()
+        .filter(|s| s.starts_with("0x"))
+        .and_then(|s| u64::from_str_radix(&s[2..], 16).ok());
+    match (number, head(network)) {
+        (Some(n), Some(h)) => n + FINALITY_DEPTH <= h,
+        _ => false,
+    }
+}
+
+fn lifetime(network: Network, method: &str, params: &Value, result: &Value) -> Option<Lifetime> {
+    let block_tagged = || head(network).map(|_| Lifetime::UntilNextHead);
+    match method {
+        "eth_chainId" => Some(Lifetime::Permanent),
+        "eth_gasPrice" => block_tagged(),
+        // Null while pending; mined ones may still be reorged until final
+        "eth_getTransactionReceipt" | "eth_getTransactionByHash" => {
+            if result.is_null() {
+                None
+            } else if is_final(network, &result["blockNumber"]) {
+                Some(Lifetime::Permanent)
+            } else {
+                block_tagged()
+            }
+        },
+        "eth_getBalance" | "eth_getTransactionCount" | "eth_getCode" | "eth_getStorageAt" | "eth_call" | "eth_getProof" => {
+            let block = params.as_array().and_then(|p| p.last()).cloned().unwrap_or(Value::Null);
+            if is_final(network, &block) {
+                return Some(Lifetime::Permanent);
+            }
+            // Deployed code cannot change, but an empty account may still be deployed to
+            if method == "eth_getCode" && result.as_str().is_some_and(|code| code.len() > 2) {
+                return Some(Lifetime::Permanent);
+            }
+            match block.as_str() {
+                Some("latest") => block_tagged(),
+                Some(s) if s.starts_with("0x") => block_tagged(),
+                // "pending", "safe", EIP-1898 block objects
+                _ => None,
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Records a new head. Block-tagged entries read at an older head expire.
+pub fn on_head(network: Network, number: u64) {
+    let advanced = HEADS.with(|h| {
+        let mut heads = h.borrow_mut();
+        let current = heads.entry(network.chain_id()).or_insert(0);
+        // Lagging endpoints must not roll the head back
+        let advanced = number > *current;
+        *current = (*current).max(number);
+        advanced
+    });
+    if advanced {
+        let prefix = format!("{}:", network.chain_id());
+        ENTRIES.with(|e| e.borrow_mut().retain(|k, entry| {
+            !k.starts_with(&prefix) || entry.head.is_none()
+        }));
+    }
+}
+
+/// A cached, still valid response.
+pub fn lookup(network: Network, key: &str) -> Option<Value> {
+    let current = head(network);
+    let hit = ENTRIES.with(|e| {
+        let mut entries = e.borrow_mut();
+        let entry = entries.get_mut(key)?;
+        if entry.head.is_some() && entry.head != current {
+            return None;
+        }
+        entry.last_used = tick();
+        Some(entry.value.clone())
+    });
+    count(|s| if hit.is_some() { s.hits += 1 } else { s.misses += 1 });
+    hit
+}
+
+pub fn insert(network: Network, method: &str, params: &Value, key: String, value: &Value) {
+    let Some(lifetime) = lifetime(network, method, params, value) else { return; };
+    let head = match lifetime {
+        Lifetime::Permanent => None,
+        Lifetime::UntilNextHead => head(network),
+    };
+    ENTRIES.with(|e| {
+        let mut entries = e.borrow_mut();
+        entries.insert(key, Entry { value: value.clone(), head, last_used: tick() });
+        if lifetime == Lifetime::Permanent {
+            DIRTY.with(|d| d.set(true));
+            evict(&mut entries);
+        }
+    });
+}
+
+fn evict(entries: &mut HashMap<String, Entry>) {
+    let permanent = entries.values().filter(|e| e.head.is_none()).count();
+    for _ in MAX_PERMANENT..permanent {
+        let oldest = entries.iter()
+            .filter(|(_, e)| e.head.is_none())
+            .min_by_key(|(_, e)| e.last_used)
+            .map(|(k, _)| k.clone());
+        if let Some(k) = oldest {
+            entries.remove(&k);
+        }
+    }
+}
+
+/// Claim on an in-flight request. Dropping it without `finish` (e.g. the
+/// caller was aborted) releases the key; joined callers then see an error.
+pub struct InFlight {
+    key: String,
+}
+
+impl InFlight {
+    /// Starts a request for `key`, or joins the identical one already running.
+    pub fn start(key: &str) -> Result<InFlight, oneshot::Receiver<Result<Value, RpcError>>> {
+        IN_FLIGHT.with(|f| {
+            let mut in_flight = f.borrow_mut();
+            match in_flight.get_mut(key) {
+                Some(waiters) => {
+                    let (tx, rx) = oneshot::channel();
+                    waiters.push(tx);
+                    count(|s| s.joined += 1);
+                    Err(rx)
+                },
+                None => {
+                    in_flight.insert(key.to_string(), Vec::new());
+                    Ok(InFlight { key: key.to_string() })
+                },
+            }
+        })
+    }
+
+    /// Hands the result to everyone who joined.
+    pub fn finish(self, result: &Result<Value, RpcError>) {
+        let waiters = IN_FLIGHT.with(|f| f.borrow_mut().remove(&self.key)).unwrap_or_default();
+        for waiter in waiters {
+            let _ = waiter.send(result.clone());
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.with(|f| f.borrow_mut().remove(&self.key));
+    }
+}
+
+pub fn stats() -> CacheStats {
+    let mut stats = STATS.with(|s| s.get());
+    stats.entries = ENTRIES.with(|e| e.borrow().len());
+    stats
+}
+
+pub fn clear() {
+    ENTRIES.with(|e| e.borrow_mut().clear());
+    STATS.with(|s| s.set(CacheStats::default()));
+    DIRTY.with(|d| d.set(false));
+}
+
+/// Whether an entry may outlive this session. Dev chain state is gone after
+/// a node restart, so their entries would come back stale.
+fn persistable(key: &str) -> bool {
+    !Network::ALL.iter()
+        .filter(|n| n.is_ephemeral())
+        .any(|n| key.starts_with(&format!("{}:", n.chain_id())))
+}
+
+/// Loads permanent entries saved by `persist`.
+pub async fn restore(store: &impl StorageBackend) {
+    let Ok(Some(saved)) = crate::storage::get::<HashMap<String, Value>>(store, CACHE_KEY).await else { return; };
+    ENTRIES.with(|e| {
+        let mut entries = e.borrow_mut();
+        for (key, value) in saved.into_iter().filter(|(key, _)| persistable(key)) {
+            entries.entry(key).or_insert(Entry { value, head: None, last_used: 0 });
+        }
+    });
+}
+
+/// Saves permanent entries if any were added since the last call.
+pub async fn persist(store: &impl StorageBackend) -> Result<(), String> {
+    if !DIRTY.with(|d| d.replace(false)) {
+        return Ok(());
+    }
+    let permanent: HashMap<String, Value> = ENTRIES.with(|e| {
+        e.borrow().iter()
+            .filter(|(k, entry)| entry.head.is_none() && persistable(k))
+            .map(|(k, entry)| (k.clone(), entry.value.clone()))
+            .collect()
+    });
+    crate::storage::set(store, CACHE_KEY, &permanent).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryBackend;
+    use futures::executor::block_on;
+    use serde_json::json;
+
+    fn cache_code(network: Network) -> String {
+        let params = json!(["0x0000000000000000000000000000000000000001", "latest"]);
+        let key = key(network, "eth_getCode", &params);
+        insert(network, "eth_getCode", &params, key.clone(), &json!("0x6080"));
+        key
+    }
+
+    #[test]
+    fn deployed_code_survives_a_reload() {
+        let store = MemoryBackend::default();
+        let key = cache_code(Network::BaseSepolia);
+        block_on(persist(&store)).unwrap();
+        clear();
+        assert_eq!(lookup(Network::BaseSepolia, &key), None);
+
+        block_on(restore(&store));
+        assert_eq!(lookup(Network::BaseSepolia, &key), Some(json!("0x6080")));
+    }
+
+    #[test]
+    fn dev_chain_entries_are_not_persisted() {
+        let store = MemoryBackend::default();
+        let kept = cache_code(Network::BaseSepolia);
+        let dropped = cache_code(Network::Anvil);
+        // Still cached for this session
+        assert_eq!(lookup(Network::Anvil, &dropped), Some(json!("0x6080")));
+
+        block_on(persist(&store)).unwrap();
+        let saved: HashMap<String, Value> = block_on(crate::storage::get(&store, CACHE_KEY)).unwrap().unwrap();
+        assert_eq!(saved.keys().collect::<Vec<_>>(), [&kept]);
+    }
+
+    #[test]
+    fn dev_chain_entries_saved_earlier_are_not_restored() {
+        let store = MemoryBackend::default();
+        let saved = HashMap::from([
+            ("31337:eth_getCode:[\"0x01\",\"latest\"]".to_string(), json!("0x6080")),
+            ("84532:eth_getCode:[\"0x01\",\"latest\"]".to_string(), json!("0x6080")),
+        ]);
+        block_on(crate::storage::set(&store, CACHE_KEY, &saved)).unwrap();
+
+        block_on(restore(&store));
+        assert_eq!(stats().entries, 1);
+        assert!(lookup(Network::BaseSepolia, "84532:eth_getCode:[\"0x01\",\"latest\"]").is_some());
+    }
+}
@@ -0,0 +1,399 @@
+use aes::cipher::{KeyIvInit, StreamCipher};
+use alloy_primitives::hex;
+use ethers_core::utils::keccak256;
+use hmac::Hmac;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+// Scrypt cost for keys we encrypt in the browser. The V3 default (n = 2^18)
+// freezes the tab for several seconds in WASM, so we use the geth "light" cost.
+const SCRYPT_LOG_N: u8 = 13;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const DKLEN: u8 = 32;
+
+// Caps on imported keystores, so a crafted file cannot exhaust memory or
+// freeze the tab. Both admit geth's standard costs (scrypt n = 2^18, r = 8).
+const MAX_SCRYPT_COST: u64 = (1 << 18) * 8;
+const MAX_PBKDF2_ROUNDS: u32 = 5_000_000;
+
+/// The `crypto` section of a Web3 Secret Storage (V3) keystore.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CryptoJson {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+    pub ciphertext: Vec<u8>,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+    pub mac: Vec<u8>,
+}
+
+/// A standard encrypted V3 keystore file, as written by geth and `cast wallet`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct V3Keystore {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    // geth before 1.4 wrote "Crypto"
+    #[serde(alias = "Crypto")]
+    pub crypto: CryptoJson,
+    pub id: String,
+    pub version: u8,
+}
+
+impl V3Keystore {
+    /// Wraps an encrypted private key; `address` is stored without 0x as geth does.
+    pub fn new(address: &str, crypto: CryptoJson) -> Self {
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        // UUID v4 variant bits
+        id[6] = (id[6] & 0x0f) | 0x40;
+        id[8] = (id[8] & 0x3f) | 0x80;
+        let h = hex::encode(id);
+        Self {
+            address: Some(address.trim_start_matches("0x").to_lowercase()),
+            crypto,
+            id: format!("{}-{}-{}-{}-{}", &h[..8], &h[8..12], &h[12..16], &h[16..20], &h[20..]),
+            version: 3,
+        }
+    }
+
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let k: V3Keystore = serde_json::from_str(json).map_err(|e| format!("Not a V3 keystore: {}", e))?;
+        if k.version != 3 {
+            return Err(format!("Unsupported keystore version {}", k.version));
+        }
+        if k.crypto.ciphertext.len() != 32 {
+            return Err("Keystore does not hold a 32-byte private key".to_string());
+        }
+        k.crypto.kdfparams.check()?;
+        Ok(k)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CipherParams {
+    #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+    pub iv: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum KdfParams {
+    Scrypt {
+        dklen: u8,
+        n: u32,
+        p: u32,
+        r: u32,
+        #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+        salt: Vec<u8>,
+    },
+    Pbkdf2 {
+        c: u32,
+        dklen: u8,
+        prf: String,
+        #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+        salt: Vec<u8>,
+    },
+}
+
+fn to_hex<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&hex::encode(bytes))
+}
+
+fn from_hex<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+    let s = String::deserialize(d)?;
+    hex::decode(s.trim_start_matches("0x")).map_err(serde::de::Error::custom)
+}
+
+impl KdfParams {
+    /// Rejects parameters we cannot or will not run.
+    fn check(&self) -> Result<(), String> {
+        match self {
+            KdfParams::Scrypt { dklen, n, p, r, .. } => {
+                if *dklen < 32 || !n.is_power_of_two() || *n < 2 || *r == 0 || *p == 0 {
+                    return Err("Unsupported scrypt parameters".to_string());
+                }
+                if *n as u64 * *r as u64 * *p as u64 > MAX_SCRYPT_COST {
+                    return Err(format!("scrypt cost too high (n = {}, r = {}, p = {}; n·r·p is capped at {})", n, r, p, MAX_SCRYPT_COST));
+                }
+            }
+            KdfParams::Pbkdf2 { c, dklen, .. } => {
+                if *dklen < 32 || *c == 0 {
+                    return Err("Unsupported PBKDF2 parameters".to_string());
+                }
+                if *c > MAX_PBKDF2_ROUNDS {
+                    return Err(format!("PBKDF2 iteration count too high ({}; capped at {})", c, MAX_PBKDF2_ROUNDS));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn derive_key(kdf: &str, password: &str, params: &KdfParams) -> Result<Zeroizing<Vec<u8>>, String> {
+    params.check()?;
+    match (kdf, params) {
+        ("scrypt", KdfParams::Scrypt { dklen, n, p, r, salt }) => {
+            let mut key = Zeroizing::new(vec![0u8; *dklen as usize]);
+            scrypt(password.as_bytes(), salt, *n as usize, *r as usize, *p as usize, &mut key);
+            Ok(key)
+        }
+        ("pbkdf2", KdfParams::Pbkdf2 { c, dklen, prf, salt }) => {
+            if prf != "hmac-sha256" {
+                return Err(format!("Unsupported PBKDF2 prf: {}", prf));
+            }
+            let mut key = Zeroizing::new(vec![0u8; *dklen as usize]);
+            pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, *c, &mut key);
+            Ok(key)
+        }
+        _ => Err(format!("Unsupported or mismatched KDF: {}", kdf)),
+    }
+}
+
+/// scrypt as in RFC 7914, minus its `n < 2^(16 r)` rule: geth does not
+/// enforce it, and the published V3 test vector (n = 2^18, r = 1) breaks it.
+/// `n` must be a power of two; `KdfParams::check` bounds n, r and p.
+fn scrypt(password: &[u8], salt: &[u8], n: usize, r: usize, p: usize, out: &mut [u8]) {
+    let block = 128 * r;
+    let mut b = Zeroizing::new(vec![0u8; block * p]);
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password, salt, 1, &mut b);
+    let mut v = Zeroizing::new(vec![0u8; block * n]);
+    let mut t = Zeroizing::new(vec![0u8; block]);
+    for chunk in b.chunks_mut(block) {
+        ro_mix(chunk, &mut v, &mut t, n);
+    }
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password, &b, 1, out);
+}
+
+fn ro_mix(b: &mut [u8], v: &mut [u8], t: &mut [u8], n: usize) {
+    let len = b.len();
+    for chunk in v.chunks_mut(len) {
+        chunk.copy_from_slice(b);
+        block_mix(chunk, b);
+    }
+    for _ in 0..n {
+        // Integerify: the first word of the last 64-byte block, mod n
+        let j = u32::from_le_bytes(b[len - 64..len - 60].try_into().unwrap()) as usize & (n - 1);
+        for ((t, b), v) in t.iter_mut().zip(b.iter()).zip(&v[j * len..(j + 1) * len]) {
+            *t = b ^ v;
+        }
+        block_mix(t, b);
+    }
+}
+
+/// BlockMix with Salsa20/8; even blocks go to the first half of `output`, odd ones to the second.
+fn block_mix(input: &[u8], output: &mut [u8]) {
+    use salsa20::cipher::{typenum::U4, StreamCipherCore};
+
+    let mut x = [0u8; 64];
+    x.copy_from_slice(&input[input.len() - 64..]);
+    for (i, chunk) in input.chunks(64).enumerate() {
+        let mut state = [0u32; 16];
+        for ((word, x), c) in state.iter_mut().zip(x.chunks_exact(4)).zip(chunk.chunks_exact(4)) {
+            *word = u32::from_le_bytes(x.try_into().unwrap()) ^ u32::from_le_bytes(c.try_into().unwrap());
+        }
+        salsa20::SalsaCore::<U4>::from_raw_state(state).write_keystream_block((&mut x).into());
+        let pos = (i / 2) * 64 + if i % 2 == 1 { input.len() / 2 } else { 0 };
+        output[pos..pos + 64].copy_from_slice(&x);
+    }
+}
+
+fn mac(key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut input = Zeroizing::new(key[16..32].to_vec());
+    input.extend_from_slice(ciphertext);
+    keccak256(&*input)
+}
+
+/// Encrypts `secret` with a passphrase (scrypt + AES-128-CTR, keccak MAC).
+pub fn encrypt(secret: &[u8], password: &str) -> Result<CryptoJson, String> {
+    let mut salt = vec![0u8; 32];
+    let mut iv = vec![0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut iv);
+
+    let kdfparams = KdfParams::Scrypt {
+        dklen: DKLEN,
+        n: 1 << SCRYPT_LOG_N,
+        p: SCRYPT_P,
+        r: SCRYPT_R,
+        salt,
+    };
+    let key = derive_key("scrypt", password, &kdfparams)?;
+
+    let mut ciphertext = secret.to_vec();
+    Aes128Ctr::new(key[..16].into(), iv[..].into()).apply_keystream(&mut ciphertext);
+
+    Ok(CryptoJson {
+        cipher: "aes-128-ctr".to_string(),
+        cipherparams: CipherParams { iv },
+        mac: mac(&key, &ciphertext).to_vec(),
+        ciphertext,
+        kdf: "scrypt".to_string(),
+        kdfparams,
+    })
+}
+
+/// Decrypts a V3 `crypto` section. A MAC mismatch means the passphrase is wrong.
+/// The secret is wiped from memory when the returned buffer is dropped.
+pub fn decrypt(crypto: &CryptoJson, password: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+    if crypto.cipher != "aes-128-ctr" {
+        return Err(format!("Unsupported cipher: {}", crypto.cipher));
+    }
+    if crypto.cipherparams.iv.len() != 16 {
+        return Err("Invalid IV length".to_string());
+    }
+
+    let key = derive_key(&crypto.kdf, password, &crypto.kdfparams)?;
+    // Constant time, so the comparison leaks nothing about the expected MAC
+    if !bool::from(mac(&key, &crypto.ciphertext)[..].ct_eq(&crypto.mac[..])) {
+        return Err("Wrong password (MAC mismatch)".to_string());
+    }
+
+    let mut secret = Zeroizing::new(crypto.ciphertext.clone());
+    Aes128Ctr::new(key[..16].into(), crypto.cipherparams.iv[..].into()).apply_keystream(&mut secret);
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from the Web3 Secret Storage definition; both encrypt this key
+    const VECTOR_PASSWORD: &str = "testpassword";
+    const VECTOR_KEY: &str = "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+
+    const PBKDF2_VECTOR: &str = r#"{
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+            "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+            "kdf": "pbkdf2",
+            "kdfparams": { "c": 262144, "dklen": 32, "prf": "hmac-sha256", "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd" },
+            "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+        },
+        "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version": 3
+    }"#;
+
+    const SCRYPT_VECTOR: &str = r#"{
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": "83dbcc02d8ccb40e466191a123791e0e" },
+            "ciphertext": "d172bf743a674da9cdad04534d56926ef8358534d458fffccd4e6ad2fbde479c",
+            "kdf": "scrypt",
+            "kdfparams": { "dklen": 32, "n": 262144, "r": 1, "p": 8, "salt": "ab0c7876052600dd703518d6fc3fe8984592145b591fc8fb5c6d43190334ba19" },
+            "mac": "2103ac29920d71da29f15d75b4a16dbe95cfd7ff8faea1056c33131d846e3097"
+        },
+        "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version": 3
+    }"#;
+
+    fn vector(json: &str) -> CryptoJson {
+        V3Keystore::parse(json).unwrap().crypto
+    }
+
+    #[test]
+    fn scrypt_matches_rfc_7914() {
+        let mut out = [0u8; 64];
+        scrypt(b"", b"", 16, 1, 1, &mut out);
+        assert_eq!(
+            hex::encode(out),
+            "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906"
+        );
+        scrypt(b"password", b"NaCl", 1024, 8, 16, &mut out);
+        assert_eq!(
+            hex::encode(out),
+            "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b3731622eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"
+        );
+    }
+
+    #[test]
+    fn pbkdf2_vector_derives_the_published_key() {
+        let crypto = vector(PBKDF2_VECTOR);
+        let key = derive_key(&crypto.kdf, VECTOR_PASSWORD, &crypto.kdfparams).unwrap();
+        assert_eq!(hex::encode(&*key), "f06d69cdc7da0faffb1008270bca38f5e31891a3a773950e6d0fea48a7188551");
+        assert_eq!(hex::encode(&*decrypt(&crypto, VECTOR_PASSWORD).unwrap()), VECTOR_KEY);
+    }
+
+    #[test]
+    fn scrypt_vector_derives_the_published_key() {
+        let crypto = vector(SCRYPT_VECTOR);
+        let key = derive_key(&crypto.kdf, VECTOR_PASSWORD, &crypto.kdfparams).unwrap();
+        assert_eq!(hex::encode(&*key), "fac192ceb5fd772906bea3e118a69e8bbb5cc24229e20d8766fd298291bba6bd");
+        assert_eq!(hex::encode(&*decrypt(&crypto, VECTOR_PASSWORD).unwrap()), VECTOR_KEY);
+    }
+
+    #[test]
+    fn encrypt_then_decrypt_round_trips() {
+        let secret = [0x42u8; 32];
+        let crypto = encrypt(&secret, "correct horse").unwrap();
+        assert_eq!(crypto.kdf, "scrypt");
+        assert_ne!(crypto.ciphertext, secret);
+        assert_eq!(decrypt(&crypto, "correct horse").unwrap().as_slice(), &secret);
+
+        // Through the V3 file format and back
+        let file = serde_json::to_string(&V3Keystore::new("0xABCD", crypto)).unwrap();
+        let parsed = V3Keystore::parse(&file).unwrap();
+        assert_eq!(parsed.address.as_deref(), Some("abcd"));
+        assert_eq!(decrypt(&parsed.crypto, "correct horse").unwrap().as_slice(), &secret);
+    }
+
+    #[test]
+    fn fresh_salt_and_iv_per_encryption() {
+        let a = encrypt(b"same secret", "pw").unwrap();
+        let b = encrypt(b"same secret", "pw").unwrap();
+        assert_ne!(a.ciphertext, b.ciphertext);
+        assert_ne!(a.cipherparams.iv, b.cipherparams.iv);
+    }
+
+    #[test]
+    fn wrong_password_fails_the_mac() {
+        let crypto = encrypt(&[1u8; 32], "right").unwrap();
+        assert_eq!(decrypt(&crypto, "wrong").unwrap_err(), "Wrong password (MAC mismatch)");
+
+        let mut tampered = crypto.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert_eq!(decrypt(&tampered, "right").unwrap_err(), "Wrong password (MAC mismatch)");
+    }
+
+    #[test]
+    fn imports_the_vector_keystores() {
+        use ethers_signers::Signer;
+        for file in [PBKDF2_VECTOR, SCRYPT_VECTOR] {
+            let k = V3Keystore::parse(file).unwrap();
+            let key = decrypt(&k.crypto, VECTOR_PASSWORD).unwrap();
+            let wallet = ethers_signers::LocalWallet::from_bytes(&key).unwrap();
+            assert_eq!(format!("{:?}", wallet.address()), "0x008aeeda4d805471df9b2a5b0f38a0c3bcba786b");
+            assert_eq!(decrypt(&k.crypto, "wrongpassword").unwrap_err(), "Wrong password (MAC mismatch)");
+        }
+    }
+
+    #[test]
+    fn rejects_costly_kdf_parameters() {
+        let scrypt = |n: u64, r: u64, p: u64| SCRYPT_VECTOR
+            .replace("\"n\": 262144, \"r\": 1, \"p\": 8", &format!("\"n\": {n}, \"r\": {r}, \"p\": {p}"));
+        assert!(V3Keystore::parse(&scrypt(1 << 18, 8, 1)).is_ok());
+        assert!(V3Keystore::parse(&scrypt(1 << 30, 8, 1)).unwrap_err().contains("scrypt cost too high"));
+        assert!(V3Keystore::parse(&scrypt(1 << 18, 8, 2)).unwrap_err().contains("scrypt cost too high"));
+        assert!(V3Keystore::parse(&scrypt(1 << 10, 1, 1 << 20)).unwrap_err().contains("scrypt cost too high"));
+        assert_eq!(V3Keystore::parse(&scrypt(1000, 8, 1)).unwrap_err(), "Unsupported scrypt parameters");
+
+        let pbkdf2 = |c: u64| PBKDF2_VECTOR.replace("\"c\": 262144", &format!("\"c\": {c}"));
+        assert!(V3Keystore::parse(&pbkdf2(2_000_000)).is_ok());
+        assert!(V3Keystore::parse(&pbkdf2(u32::MAX as u64)).unwrap_err().contains("PBKDF2 iteration count too high"));
+        assert_eq!(V3Keystore::parse(&pbkdf2(0)).unwrap_err(), "Unsupported PBKDF2 parameters");
+
+        // decrypt checks too, for crypto sections that skipped parse
+        let mut crypto = vector(PBKDF2_VECTOR);
+        crypto.kdfparams = KdfParams::Pbkdf2 { c: u32::MAX, dklen: 32, prf: "hmac-sha256".to_string(), salt: vec![0; 32] };
+        assert!(decrypt(&crypto, VECTOR_PASSWORD).unwrap_err().contains("PBKDF2 iteration count too high"));
+    }
+}
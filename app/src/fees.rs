@@ -0,0 +1,316 @@
+use std::cell::Cell;
+
+use ethers_core::types::U256;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::amount::Amount;
+use crate::rpc::{Batch, BatchResponse, Network, Pending, RpcError};
+
+/// Blocks of history the suggestions are based on.
+const HISTORY_BLOCKS: u64 = 10;
+/// Reward percentiles requested from `eth_feeHistory`, one per `Speed`.
+const PERCENTILES: [f64; 3] = [10.0, 50.0, 90.0];
+/// Tip floor for chains whose blocks carry no tips at all (L2s, Anvil), in wei.
+const MIN_PRIORITY_FEE: u64 = 1_000_000;
+
+/// How soon the user wants a transaction included.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Speed {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+}
+
+impl Speed {
+    pub const ALL: [Speed; 3] = [Speed::Slow, Speed::Normal, Speed::Fast];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Speed::Slow => "Slow",
+            Speed::Normal => "Normal",
+            Speed::Fast => "Fast",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Speed::Slow => 0,
+            Speed::Normal => 1,
+            Speed::Fast => 2,
+        }
+    }
+
+    /// Base fee headroom in percent. The base fee can rise 12.5% per block,
+    /// so a faster pick also survives a longer run of full blocks.
+    fn base_fee_percent(&self) -> u64 {
+        match self {
+            Speed::Slow => 125,
+            Speed::Normal => 150,
+            Speed::Fast => 200,
+        }
+    }
+}
+
+/// EIP-1559 fee caps for one speed, per unit of gas.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FeeSuggestion {
+    pub max_fee_per_gas: Amount,
+    pub max_priority_fee_per_gas: Amount,
+    /// Price per gas at today's base fee: the base fee plus the tip.
+    /// Legacy transactions bid exactly this, as they always pay their full price.
+    pub price: Amount,
+}
+
+impl FeeSuggestion {
+    /// Caps for sending a whole balance. The headroom is cut to one block's
+    /// base fee rise, since a sweep reserves the full cap for gas and whatever
+    /// the cap exceeds the price actually paid by stays behind as dust.
+    pub fn sweep(&self) -> FeeSuggestion {
+        let cap = self.price.mul_div(9, 8).unwrap_or(self.price);
+        let max_fee_per_gas = if cap < self.max_fee_per_gas { cap } else { self.max_fee_per_gas };
+        FeeSuggestion { max_fee_per_gas, ..*self }
+    }
+}
+
+/// Suggestions for every speed, from one look at recent blocks.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FeeEstimate {
+    /// Base fee of the next block
+    pub base_fee: Amount,
+    suggestions: [FeeSuggestion; 3],
+}
+
+impl FeeEstimate {
+    pub fn get(&self, speed: Speed) -> FeeSuggestion {
+        self.suggestions[speed.index()]
+    }
+
+    fn from_history(history: FeeHistory) -> Self {
+        let suggestions = Speed::ALL.map(|speed| {
+            // Median tip paid at this percentile, so one odd block does not skew it
+            let mut tips: Vec<U256> = history.rewards.iter().filter_map(|r| r.get(speed.index()).copied()).collect();
+            tips.sort();
+            let tip = tips.get(tips.len() / 2).copied().unwrap_or_default().max(U256::from(MIN_PRIORITY_FEE));
+            let priority = Amount::wei(tip);
+            let headroom = history.base_fee.mul_div(speed.base_fee_percent(), 100).unwrap_or(history.base_fee);
+            FeeSuggestion {
+                max_fee_per_gas: headroom.checked_add(priority).unwrap_or(headroom),
+                max_priority_fee_per_gas: priority,
+                price: history.base_fee.checked_add(priority).unwrap_or(history.base_fee),
+            }
+        });
+        Self { base_fee: history.base_fee, suggestions }
+    }
+
+    // Nodes without eth_feeHistory: scale eth_gasPrice, which already includes a tip
+    fn from_gas_price(gas_price: u128) -> Self {
+        let price = Amount::wei(gas_price);
+        let suggestions = [100, 110, 120].map(|percent| {
+            let fee = price.mul_div(percent, 100).unwrap_or(price);
+            FeeSuggestion { max_fee_per_gas: fee, max_priority_fee_per_gas: fee, price: fee }
+        });
+        Self { base_fee: price, suggestions }
+    }
+}
+
+struct FeeHistory {
+    base_fee: Amount,
+    /// Per block, one tip per entry of `PERCENTILES`
+    rewards: Vec<Vec<U256>>,
+}
+
+fn decode_fee_history(val: serde_json::Value) -> Result<FeeHistory, RpcError> {
+    // `baseFeePerGas` has one more entry than blocks: the next block's
+    let base_fee = val["baseFeePerGas"].as_array().and_then(|fees| fees.last()).cloned()
+        .ok_or(RpcError::Decode("feeHistory without baseFeePerGas".to_string()))?;
+    let base_fee = Amount::wei(crate::rpc::decode_u256(base_fee)?);
+    let mut rewards = Vec::new();
+    for block in val["reward"].as_array().into_iter().flatten() {
+        let tips = block.as_array().into_iter().flatten().map(|t| crate::rpc::decode_u256(t.clone()));
+        rewards.push(tips.collect::<Result<Vec<_>, _>>()?);
+    }
+    Ok(FeeHistory { base_fee, rewards })
+}
+
+/// A fee estimate queued in a `Batch`, so it can share a round trip.
+pub struct FeeRequest {
+    history: Pending<FeeHistory>,
+    gas_price: Pending<u128>,
+}
+
+impl FeeRequest {
+    pub fn queue(batch: &mut Batch) -> Self {
+        let history = batch.call("eth_feeHistory", json!([format!("0x{:x}", HISTORY_BLOCKS), "latest", PERCENTILES]), decode_fee_history);
+        Self { history, gas_price: batch.gas_price() }
+    }
+
+    pub fn redeem(self, resp: &mut BatchResponse) -> Result<FeeEstimate, RpcError> {
+        let gas_price = resp.get(self.gas_price);
+        match resp.get(self.history) {
+            Ok(history) => Ok(FeeEstimate::from_history(history)),
+            Err(e) => gas_price.map(FeeEstimate::from_gas_price).map_err(|_| e),
+        }
+    }
+}
+
+pub async fn estimate(network: Network) -> Result<FeeEstimate, RpcError> {
+    let mut batch = Batch::new();
+    let request = FeeRequest::queue(&mut batch);
+    request.redeem(&mut batch.send(network).await?)
+}
+
+/// Caps for the user's chosen speed.
+pub async fn suggest(network: Network) -> Result<FeeSuggestion, RpcError> {
+    Ok(estimate(network).await?.get(speed()))
+}
+
+thread_local! {
+    static SPEED: Cell<Speed> = const { Cell::new(Speed::Normal) };
+}
+
+/// Speed used by every transaction builder (see `Settings::fee_speed`).
+pub fn set_speed(speed: Speed) {
+    SPEED.with(|s| s.set(speed));
+}
+
+pub fn speed() -> Speed {
+    SPEED.with(|s| s.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{set_transport, AnyTransport, MockTransport};
+    use futures::executor::block_on;
+    use serde_json::Value;
+
+    const GWEI: u64 = 1_000_000_000;
+
+    fn hex(n: u64) -> Value {
+        json!(format!("0x{:x}", n))
+    }
+
+    /// A node whose last blocks paid `rewards` (one tip per percentile) and
+    /// whose next block has `base_fee`.
+    fn node(base_fee: u64, rewards: &[[u64; 3]]) -> MockTransport {
+        let mock = MockTransport::new();
+        let mut base_fees = vec![hex(base_fee / 2); rewards.len()];
+        base_fees.push(hex(base_fee));
+        let rewards: Vec<Value> = rewards.iter().map(|r| json!(r.map(hex))).collect();
+        mock.respond("eth_feeHistory", json!({ "oldestBlock": "0x1", "baseFeePerGas": base_fees, "reward": rewards }));
+        mock.respond("eth_gasPrice", hex(99 * GWEI));
+        set_transport(AnyTransport::Mock(mock.clone()));
+        mock
+    }
+
+    fn wei(n: u64) -> Amount {
+        Amount::wei(n)
+    }
+
+    #[test]
+    fn tips_are_the_median_per_percentile() {
+        node(GWEI, &[
+            [2_000_000, 30_000_000, 900_000_000],
+            [4_000_000, 10_000_000, 500_000_000],
+            [3_000_000, 20_000_000, 700_000_000],
+        ]);
+        let fees = block_on(estimate(Network::Anvil)).unwrap();
+        assert_eq!(fees.base_fee, wei(GWEI));
+        assert_eq!(fees.get(Speed::Slow).max_priority_fee_per_gas, wei(3_000_000));
+        assert_eq!(fees.get(Speed::Normal).max_priority_fee_per_gas, wei(20_000_000));
+        assert_eq!(fees.get(Speed::Fast).max_priority_fee_per_gas, wei(700_000_000));
+    }
+
+    #[test]
+    fn tips_have_a_floor() {
+        node(GWEI, &[[0, 0, 0], [0, 0, 5_000_000]]);
+        let fees = block_on(estimate(Network::Anvil)).unwrap();
+        assert_eq!(fees.get(Speed::Slow).max_priority_fee_per_gas, wei(MIN_PRIORITY_FEE));
+        assert_eq!(fees.get(Speed::Normal).max_priority_fee_per_gas, wei(MIN_PRIORITY_FEE));
+        assert_eq!(fees.get(Speed::Fast).max_priority_fee_per_gas, wei(5_000_000));
+    }
+
+    #[test]
+    fn faster_speeds_leave_more_base_fee_headroom() {
+        let tip = 2_000_000;
+        node(GWEI, &[[tip, tip, tip]]);
+        let fees = block_on(estimate(Network::Anvil)).unwrap();
+        assert_eq!(fees.get(Speed::Slow).max_fee_per_gas, wei(GWEI * 125 / 100 + tip));
+        assert_eq!(fees.get(Speed::Normal).max_fee_per_gas, wei(GWEI * 150 / 100 + tip));
+        assert_eq!(fees.get(Speed::Fast).max_fee_per_gas, wei(GWEI * 2 + tip));
+
+        for speed in Speed::ALL {
+            assert_eq!(fees.get(speed).price, wei(GWEI + tip));
+        }
+
+        set_speed(Speed::Fast);
+        assert_eq!(block_on(suggest(Network::Anvil)).unwrap(), fees.get(Speed::Fast));
+    }
+
+    #[test]
+    fn sweeps_reserve_one_block_of_base_fee_rise() {
+        let tip = 2_000_000;
+        node(GWEI, &[[tip, tip, tip]]);
+        let fees = block_on(estimate(Network::Anvil)).unwrap();
+        for speed in Speed::ALL {
+            let sweep = fees.get(speed).sweep();
+            assert_eq!(sweep.max_fee_per_gas, wei((GWEI + tip) * 9 / 8));
+            // The tip is left as suggested, not raised to the cap
+            assert_eq!(sweep.max_priority_fee_per_gas, wei(tip));
+        }
+
+        // Never above the speed's own cap
+        let big_tip = 3 * GWEI;
+        node(GWEI, &[[big_tip, big_tip, big_tip]]);
+        let slow = block_on(estimate(Network::Anvil)).unwrap().get(Speed::Slow);
+        assert_eq!(slow.sweep().max_fee_per_gas, slow.max_fee_per_gas);
+    }
+
+    #[test]
+    fn falls_back_to_gas_price() {
+        let mock = MockTransport::new();
+        mock.fail("eth_feeHistory", -32601, "the method eth_feeHistory does not exist");
+        mock.respond("eth_gasPrice", hex(2 * GWEI));
+        set_transport(AnyTransport::Mock(mock.clone()));
+        let fees = block_on(estimate(Network::Anvil)).unwrap();
+        assert_eq!(fees.base_fee, wei(2 * GWEI));
+        for (speed, price) in Speed::ALL.into_iter().zip([2 * GWEI, 2_200_000_000, 2_400_000_000]) {
+            let fee = fees.get(speed);
+            assert_eq!(fee, FeeSuggestion { max_fee_per_gas: wei(price), max_priority_fee_per_gas: wei(price), price: wei(price) });
+            assert_eq!(fee.sweep(), fee);
+        }
+        let methods: Vec<Value> = mock.requests().iter().map(|r| r["method"].clone()).collect();
+        assert!(methods.contains(&json!("eth_feeHistory")) && methods.contains(&json!("eth_gasPrice")));
+
+        // Without either, the feeHistory error is what surfaces
+        let mock = MockTransport::new();
+        mock.fail("eth_feeHistory", -32601, "the method eth_feeHistory does not exist");
+        mock.fail("eth_gasPrice", -32000, "gas price unavailable");
+        set_transport(AnyTransport::Mock(mock));
+        let err = block_on(estimate(Network::Anvil)).unwrap_err();
+        assert!(err.to_string().contains("eth_feeHistory"), "{}", err);
+    }
+
+    #[test]
+    fn empty_rewards_fall_back_to_the_floor() {
+        node(GWEI, &[]);
+        let fees = block_on(estimate(Network::Anvil)).unwrap();
+        for speed in Speed::ALL {
+            assert_eq!(fees.get(speed).max_priority_fee_per_gas, wei(MIN_PRIORITY_FEE));
+        }
+
+        // Nodes that send empty per-block arrays, or omit `reward` entirely
+        let mock = MockTransport::new();
+        mock.respond("eth_feeHistory", json!({ "baseFeePerGas": [hex(GWEI)], "reward": [[], []] }));
+        mock.respond("eth_feeHistory", json!({ "baseFeePerGas": [hex(GWEI)] }));
+        mock.respond("eth_gasPrice", hex(99 * GWEI));
+        set_transport(AnyTransport::Mock(mock.clone()));
+        let fees = block_on(estimate(Network::Anvil)).unwrap();
+        assert_eq!(fees.get(Speed::Normal).max_fee_per_gas, wei(GWEI * 3 / 2 + MIN_PRIORITY_FEE));
+        let fees = block_on(estimate(Network::Anvil)).unwrap();
+        assert_eq!(fees.get(Speed::Normal).max_priority_fee_per_gas, wei(MIN_PRIORITY_FEE));
+    }
+}
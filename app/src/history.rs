@@ -0,0 +1,643 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use ethers_core::types::U256;
+use leptos::*;
+use leptos_router::A;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::amount::Amount;
+use crate::rpc::{Batch, Network, RpcError, TRANSFER_TOPIC};
+use crate::settings::Settings;
+use crate::storage::Backend;
+use crate::vault::Vault;
+
+/// IndexedDB database holding one history per (network, wallet).
+const HISTORY_DB: &str = "diamond_wallet_history";
+/// keccak256("CallExecuted(address,uint256,bytes)"), emitted by the TBA's WalletFacet
+const CALL_EXECUTED_TOPIC: &str = "0x7aa5ed2c76d4b9b3e8cbc2d86e798d468acf8cc22876dbfe0b62ea3180006c26";
+/// How far back the first backfill of a wallet looks.
+const INITIAL_LOOKBACK: u64 = 10_000;
+/// Block range per eth_getLogs; public endpoints reject wide ranges.
+const CHUNK_BLOCKS: u64 = 2_000;
+const MAX_CHUNKS_PER_RUN: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TxKind {
+    Send,
+    /// ETH sent by the TBA on the signer's behalf
+    TbaSend,
+    Sweep,
+    Mint,
+    Sponsor,
+    /// Funds returned while clearing the wallet
+    Drain,
+    /// ERC-20 `Transfer` from or to one of our addresses
+    TokenTransfer,
+    /// ERC-721 `Transfer` (same event, token id indexed)
+    NftTransfer,
+    /// `CallExecuted` emitted by our TBA
+    TbaCall,
+}
+
+impl TxKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TxKind::Send => "Send",
+            TxKind::TbaSend => "Send via TBA",
+            TxKind::Sweep => "Sweep",
+            TxKind::Mint => "Mint",
+            TxKind::Sponsor => "Sponsor",
+            TxKind::Drain => "Drain",
+            TxKind::TokenTransfer => "Token Transfer",
+            TxKind::NftTransfer => "NFT Transfer",
+            TxKind::TbaCall => "TBA Call",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TxStatus {
+    Pending,
+    Confirmed,
+    Failed,
+}
+
+/// One line of activity: a transaction we sent, or a log that concerns us.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TxRecord {
+    pub hash: String,
+    pub kind: TxKind,
+    /// Set for log entries, since one transaction can emit several
+    pub log_index: Option<u64>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Wei, token base units, or the NFT id
+    pub value: Option<U256>,
+    /// Token contract of transfer entries
+    pub token: Option<String>,
+    /// Token decimals, when the contract reported them
+    pub decimals: Option<u8>,
+    pub status: TxStatus,
+    pub block_number: Option<u64>,
+    pub gas_used: Option<U256>,
+    pub effective_gas_price: Option<U256>,
+    /// Send to confirmation, from `LatencyTracker`
+    pub latency_ms: Option<f64>,
+    /// When we broadcast it (ms since epoch); backfilled entries have none
+    pub sent_at: Option<f64>,
+}
+
+impl TxRecord {
+    pub fn sent(hash: String, kind: TxKind, from: String, to: Option<String>, value: Option<U256>) -> Self {
+        Self {
+            hash,
+            kind,
+            log_index: None,
+            from: Some(from),
+            to,
+            value,
+            token: None,
+            decimals: None,
+            status: TxStatus::Pending,
+            block_number: None,
+            gas_used: None,
+            effective_gas_price: None,
+            latency_ms: None,
+            sent_at: Some(crate::transport::now_ms()),
+        }
+    }
+
+    /// Total fee paid, once the receipt is in.
+    pub fn fee(&self) -> Option<Amount> {
+        Amount::wei(self.gas_used?).checked_mul(self.effective_gas_price?)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct History {
+    pub records: Vec<TxRecord>,
+    /// Last block the log backfill has covered
+    pub scanned_to: Option<u64>,
+}
+
+impl History {
+    /// Adds `record` unless it is already known. Logs of a transaction we
+    /// sent ourselves are dropped; the sent record already describes it,
+    /// except for what our TBA forwarded, which only its `CallExecuted` shows.
+    fn insert(&mut self, record: TxRecord) -> bool {
+        if record.kind == TxKind::TbaCall {
+            let sent = self.records.iter_mut().find(|r| r.log_index.is_none() && r.hash.eq_ignore_ascii_case(&record.hash));
+            if let Some(sent) = sent.filter(|r| r.value.unwrap_or_default().is_zero()) {
+                sent.to = record.to;
+                sent.value = record.value;
+                return false;
+            }
+        }
+        let known = self.records.iter().any(|r| {
+            r.hash.eq_ignore_ascii_case(&record.hash) && (r.log_index == record.log_index || r.log_index.is_none())
+        });
+        if !known {
+            self.records.push(record);
+        }
+        !known
+    }
+
+    /// Newest first; pending entries on top.
+    pub fn sorted(&self) -> Vec<TxRecord> {
+        let mut records = self.records.clone();
+        records.sort_by(|a, b| {
+            let block = |r: &TxRecord| r.block_number.unwrap_or(u64::MAX);
+            block(b).cmp(&block(a))
+                .then(b.log_index.cmp(&a.log_index))
+                .then(b.sent_at.unwrap_or(0.0).total_cmp(&a.sent_at.unwrap_or(0.0)))
+        });
+        records
+    }
+}
+
+thread_local! {
+    /// Loaded histories by storage key, so concurrent updates do not overwrite each other
+    static HISTORIES: RefCell<HashMap<String, History>> = RefCell::new(HashMap::new());
+}
+
+fn store() -> Backend {
+    Backend::detect_large(HISTORY_DB)
+}
+
+fn key(network: Network, owner: &str) -> String {
+    format!("history:{}:{}", network.chain_id(), owner.to_lowercase())
+}
+
+async fn ensure_loaded(key: &str) {
+    if HISTORIES.with(|h| h.borrow().contains_key(key)) {
+        return;
+    }
+    let loaded = crate::storage::get::<History>(&store(), key).await.ok().flatten().unwrap_or_default();
+    HISTORIES.with(|h| {
+        h.borrow_mut().entry(key.to_string()).or_insert(loaded);
+    });
+}
+
+/// A copy of the stored history.
+pub async fn snapshot(network: Network, owner: &str) -> History {
+    let key = key(network, owner);
+    ensure_loaded(&key).await;
+    HISTORIES.with(|h| h.borrow().get(&key).cloned().unwrap_or_default())
+}
+
+async fn update<R>(network: Network, owner: &str, f: impl FnOnce(&mut History) -> R) -> R {
+    let key = key(network, owner);
+    ensure_loaded(&key).await;
+    let (result, history) = HISTORIES.with(|h| {
+        let mut histories = h.borrow_mut();
+        let history = histories.entry(key.clone()).or_default();
+        (f(history), history.clone())
+    });
+    if let Err(e) = crate::storage::set(&store(), &key, &history).await {
+        leptos::logging::warn!("History not saved: {}", e);
+    }
+    result
+}
+
+/// Records a transaction `owner` (its signer) just broadcast.
+pub async fn record_sent(network: Network, owner: &str, record: TxRecord) {
+    update(network, owner, |h| h.insert(record)).await;
+}
+
+/// Fills in the receipt of a transaction `owner` sent. The owner is passed
+/// rather than read from the active wallet, which may have changed meanwhile.
+pub async fn record_receipt(network: Network, owner: &str, hash: &str, receipt: &Value, latency_ms: Option<f64>) {
+    update(network, owner, |h| apply_receipt(h, hash, receipt, latency_ms)).await;
+}
+
+fn apply_receipt(history: &mut History, hash: &str, receipt: &Value, latency_ms: Option<f64>) {
+    let quantity = |field: &str| crate::rpc::decode_u256(receipt[field].clone()).ok();
+    let Some(record) = history.records.iter_mut().find(|r| r.log_index.is_none() && r.hash.eq_ignore_ascii_case(hash)) else { return; };
+    record.status = if receipt["status"].as_str() == Some("0x1") { TxStatus::Confirmed } else { TxStatus::Failed };
+    record.block_number = quantity("blockNumber").map(|n| n.low_u64());
+    record.gas_used = quantity("gasUsed");
+    record.effective_gas_price = quantity("effectiveGasPrice");
+    if latency_ms.is_some() {
+        record.latency_ms = latency_ms;
+    }
+}
+
+/// `address` as a 32-byte log topic.
+fn topic(address: &str) -> String {
+    format!("0x{:0>64}", address.trim_start_matches("0x").to_lowercase())
+}
+
+fn topic_address(topic: &Value) -> Option<String> {
+    let topic = topic.as_str()?;
+    Some(format!("0x{}", &topic[topic.len().checked_sub(40)?..]))
+}
+
+fn decode_logs(val: Value) -> Result<Vec<Value>, RpcError> {
+    match val {
+        Value::Array(logs) => Ok(logs),
+        other => Err(RpcError::Decode(format!("expected log array, got {}", other))),
+    }
+}
+
+fn record_from_log(log: &Value) -> Option<TxRecord> {
+    let topics = log["topics"].as_array()?;
+    let quantity = |v: &Value| crate::rpc::decode_u256(v.clone()).ok();
+    let data = alloy_primitives::hex::decode(log["data"].as_str()?.trim_start_matches("0x")).ok()?;
+    let first_word = data.get(..32).map(U256::from_big_endian);
+
+    let (kind, from, to, value, token) = match topics.first()?.as_str()? {
+        TRANSFER_TOPIC if topics.len() == 4 => {
+            (TxKind::NftTransfer, topic_address(&topics[1]), topic_address(&topics[2]), quantity(&topics[3]), log["address"].as_str())
+        },
+        TRANSFER_TOPIC if topics.len() == 3 => {
+            (TxKind::TokenTransfer, topic_address(&topics[1]), topic_address(&topics[2]), first_word, log["address"].as_str())
+        },
+        CALL_EXECUTED_TOPIC => {
+            (TxKind::TbaCall, log["address"].as_str().map(str::to_string), topics.get(1).and_then(topic_address), first_word, None)
+        },
+        _ => return None,
+    };
+    Some(TxRecord {
+        hash: log["transactionHash"].as_str()?.to_string(),
+        kind,
+        log_index: quantity(&log["logIndex"]).map(|n| n.low_u64()),
+        from,
+        to,
+        value,
+        token: token.map(str::to_string),
+        decimals: None,
+        status: TxStatus::Confirmed,
+        block_number: quantity(&log["blockNumber"]).map(|n| n.low_u64()),
+        gas_used: None,
+        effective_gas_price: None,
+        latency_ms: None,
+        sent_at: None,
+    })
+}
+
+async fn token_decimals(network: Network, token: &str) -> Option<u8> {
+    let decimals_fn = crate::abi::ContractFn::parse("function decimals() view returns (uint8)").ok()?;
+    let out = crate::rpc::call_contract(network, token, &decimals_fn, &[], crate::rpc::BlockTag::Latest).await.ok()?;
+    out.into_iter().next()?.into_uint().map(|d| d.low_u32().min(u8::MAX as u32) as u8)
+}
+
+/// Picks up receipts of pending transactions and scans new blocks for
+/// transfers and TBA calls concerning `owner` or `tba`. Resumes where the
+/// last run stopped; returns how many entries were added.
+///
+/// Plain ETH transfers emit no logs, so ETH others sent straight to `owner`
+/// or `tba` is not found; only our own sends record ETH movements.
+pub async fn backfill(network: Network, owner: &str, tba: Option<&str>) -> Result<usize, RpcError> {
+    let history = snapshot(network, owner).await;
+
+    // 1. Receipts of transactions still pending (e.g. the tab closed while waiting)
+    for record in history.records.iter().filter(|r| r.status == TxStatus::Pending && r.log_index.is_none()) {
+        let receipt = crate::rpc::get_transaction_receipt(&record.hash, network).await?;
+        if !receipt.is_null() {
+            update(network, owner, |h| apply_receipt(h, &record.hash, &receipt, None)).await;
+        }
+    }
+
+    // 2. Logs, chunk by chunk, saving progress after each
+    let head = crate::rpc::get_block_number(network).await?;
+    let mut from = history.scanned_to.map(|b| b + 1).unwrap_or(head.saturating_sub(INITIAL_LOOKBACK));
+    let ours: Vec<String> = std::iter::once(owner).chain(tba).map(topic).collect();
+    let mut decimals: HashMap<String, Option<u8>> = HashMap::new();
+    let mut added = 0;
+    for _ in 0..MAX_CHUNKS_PER_RUN {
+        if from > head {
+            break;
+        }
+        let to = (from + CHUNK_BLOCKS - 1).min(head);
+        let range = |mut filter: Value| {
+            filter["fromBlock"] = json!(format!("0x{:x}", from));
+            filter["toBlock"] = json!(format!("0x{:x}", to));
+            json!([filter])
+        };
+
+        let mut batch = Batch::new();
+        let incoming = batch.call("eth_getLogs", range(json!({ "topics": [TRANSFER_TOPIC, null, ours] })), decode_logs);
+        let outgoing = batch.call("eth_getLogs", range(json!({ "topics": [TRANSFER_TOPIC, ours, null] })), decode_logs);
+        let calls = tba.map(|tba| batch.call("eth_getLogs", range(json!({ "address": tba, "topics": [CALL_EXECUTED_TOPIC] })), decode_logs));
+        let mut resp = batch.send(network).await?;
+        let mut logs = resp.get(incoming)?;
+        logs.extend(resp.get(outgoing)?);
+        if let Some(calls) = calls {
+            logs.extend(resp.get(calls)?);
+        }
+
+        let mut records = Vec::new();
+        for log in &logs {
+            let Some(mut record) = record_from_log(log) else { continue; };
+            if let (TxKind::TokenTransfer, Some(token)) = (record.kind, record.token.clone()) {
+                if !decimals.contains_key(&token) {
+                    decimals.insert(token.clone(), token_decimals(network, &token).await);
+                }
+                record.decimals = decimals[&token];
+            }
+            records.push(record);
+        }
+        added += update(network, owner, |h| {
+            h.scanned_to = Some(to);
+            records.into_iter().filter(|r| h.insert(r.clone())).count()
+        }).await;
+        from = to + 1;
+    }
+    Ok(added)
+}
+
+fn short(hex: &str) -> String {
+    if hex.len() <= 12 { hex.to_string() } else { format!("{}...{}", &hex[..6], &hex[hex.len() - 4..]) }
+}
+
+fn amount_text(record: &TxRecord) -> String {
+    let Some(value) = record.value else { return String::new(); };
+    match record.kind {
+        TxKind::NftTransfer => format!("#{}", value),
+        TxKind::TokenTransfer => {
+            let token = record.token.as_deref().map(short).unwrap_or_default();
+            match record.decimals.and_then(|d| Amount::new(value, d).ok()) {
+                Some(amount) => format!("{} {}", amount.format_locale(4), token),
+                None => format!("{} units of {}", value, token),
+            }
+        },
+        _ => format!("{} ETH", Amount::wei(value).format_locale(4)),
+    }
+}
+
+/// Activity of the active wallet on the selected network.
+#[component]
+pub fn HistoryView() -> impl IntoView {
+    let (records, set_records) = create_signal(Vec::<TxRecord>::new());
+    let (status, set_status) = create_signal("Loading...".to_string());
+    // Network, signer and TBA of the wallet the wallet view last opened
+    let (context, set_context) = create_signal(None::<(Network, String, Option<String>)>);
+    let scanning = store_value(false);
+
+    create_effect(move |_| {
+        spawn_local(async move {
+            let store = Backend::detect("diamond_wallet");
+            let network = Settings::load(&store).await.network;
+            match Vault::load(&store).await.ok().and_then(|v| v.active_keystore()) {
+                Some(k) if !k.address.is_empty() => set_context.set(Some((network, k.address, k.smart_account))),
+                _ => set_status.set("No wallet yet".to_string()),
+            }
+        });
+    });
+
+    let scan = move || {
+        let Some((network, owner, tba)) = context.get_untracked() else { return; };
+        if scanning.get_value() { return; }
+        scanning.set_value(true);
+        spawn_local(async move {
+            set_records.set(snapshot(network, &owner).await.sorted());
+            set_status.set("Scanning...".to_string());
+            match backfill(network, &owner, tba.as_deref()).await {
+                Ok(added) => set_status.set(format!("Up to date on {} ({} new)", network.name(), added)),
+                Err(e) => set_status.set(format!("Scan paused: {}", e.label())),
+            }
+            set_records.set(snapshot(network, &owner).await.sorted());
+            scanning.set_value(false);
+        });
+    };
+
+    create_effect(move |_| {
+        if context.get().is_some() {
+            scan();
+        }
+    });
+    let timer = set_interval_with_handle(scan, std::time::Duration::from_secs(15)).ok();
+    on_cleanup(move || {
+        if let Some(t) = timer { t.clear(); }
+    });
+
+    view! {
+        <div class="wallet-container">
+            <header class="app-header">
+                <div class="header-status">
+                    <div style="color:#4CAF50; font-size:12px; font-weight:bold;">{move || status.get()}</div>
+                </div>
+                <div class="header-icons">
+                    <A href="/" class="wallet-btn">"Wallet"</A>
+                </div>
+            </header>
+
+            <h3 class="modal-title">"History"</h3>
+            <p class="tiny-text" style="text-align:center; color:#888;">"ETH received by plain transfer is not listed: it leaves no log to find it by."</p>
+            {move || {
+                let records = records.get();
+                if records.is_empty() {
+                    return view! { <p class="tiny-text" style="text-align:center;">"No activity yet"</p> }.into_view();
+                }
+                let (network, owner, tba) = context.get().unwrap_or_default();
+                let ours = move |addr: &Option<String>| addr.as_deref().is_some_and(|a| {
+                    a.eq_ignore_ascii_case(&owner) || tba.as_deref().is_some_and(|t| a.eq_ignore_ascii_case(t))
+                });
+                records.into_iter().map(|r| {
+                    let incoming = ours(&r.to) && !ours(&r.from);
+                    let status = match r.status {
+                        TxStatus::Pending => "pending".to_string(),
+                        TxStatus::Confirmed => r.block_number.map(|b| format!("block {}", b)).unwrap_or("confirmed".to_string()),
+                        TxStatus::Failed => "failed".to_string(),
+                    };
+                    let details = [
+                        r.fee().map(|fee| format!("fee {} ETH", fee.format_locale(6))),
+                        r.latency_ms.map(|ms| format!("{:.0}ms", ms)),
+                    ].into_iter().flatten().collect::<Vec<_>>().join(" · ");
+                    let hash = short(&r.hash);
+                    view! {
+                        <div class="bal-item" style="display:block; padding:6px 0; border-bottom:1px dashed #333;">
+                            <div class="flex-row" style="justify-content:space-between;">
+                                <span>{format!("{} {}", if incoming { "↓" } else { "↑" }, r.kind.label())}</span>
+                                <span class="val">{amount_text(&r)}</span>
+                            </div>
+                            <div class="flex-row tiny-text" style="justify-content:space-between; color:#888;">
+                                {match network.tx_url(&r.hash) {
+                                    Some(url) => view! { <a href=url target="_blank" rel="noopener">{hash}</a> }.into_view(),
+                                    None => view! { <span>{hash}</span> }.into_view(),
+                                }}
+                                <span>{status}</span>
+                            </div>
+                            <div class="tiny-text" style="color:#888;">{details}</div>
+                        </div>
+                    }
+                }).collect_view()
+            }}
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    const ALICE: &str = "0x00000000000000000000000000000000000000a1";
+    const BOB: &str = "0x00000000000000000000000000000000000000b2";
+
+    #[test]
+    fn receipt_lands_in_the_sender_history() {
+        let network = Network::BaseSepolia;
+        block_on(record_sent(network, ALICE, TxRecord::sent("0xaa".to_string(), TxKind::Send, ALICE.to_string(), None, None)));
+        // Bob's wallet is active meanwhile; the receipt is still Alice's
+        block_on(snapshot(network, BOB));
+        let receipt = json!({ "status": "0x1", "blockNumber": "0x10", "gasUsed": "0x5208", "effectiveGasPrice": "0x1" });
+        block_on(record_receipt(network, ALICE, "0xAA", &receipt, Some(1500.0)));
+
+        let alice = block_on(snapshot(network, &ALICE.to_uppercase().replace("0X", "0x")));
+        assert_eq!(alice.records.len(), 1);
+        assert_eq!(alice.records[0].status, TxStatus::Confirmed);
+        assert_eq!(alice.records[0].block_number, Some(16));
+        assert_eq!(alice.records[0].latency_ms, Some(1500.0));
+        assert!(block_on(snapshot(network, BOB)).records.is_empty());
+    }
+
+    #[test]
+    fn receipt_for_another_owner_is_ignored() {
+        let network = Network::BaseSepolia;
+        block_on(record_sent(network, ALICE, TxRecord::sent("0xaa".to_string(), TxKind::Send, ALICE.to_string(), None, None)));
+        block_on(record_receipt(network, BOB, "0xaa", &json!({ "status": "0x0" }), None));
+
+        assert_eq!(block_on(snapshot(network, ALICE)).records[0].status, TxStatus::Pending);
+        assert!(block_on(snapshot(network, BOB)).records.is_empty());
+    }
+
+    fn transfer_log(hash: &str, log_index: u64, topics: Vec<String>, data: &str) -> Value {
+        json!({
+            "address": "0x00000000000000000000000000000000000000c3",
+            "topics": topics,
+            "data": data,
+            "transactionHash": hash,
+            "logIndex": format!("0x{:x}", log_index),
+            "blockNumber": "0x20",
+        })
+    }
+
+    fn word(n: u64) -> String {
+        format!("0x{:064x}", n)
+    }
+
+    #[test]
+    fn transfer_logs_are_told_apart_by_topic_count() {
+        let erc20 = transfer_log("0xcc", 1, vec![TRANSFER_TOPIC.to_string(), topic(BOB), topic(ALICE)], &word(500));
+        let record = record_from_log(&erc20).unwrap();
+        assert_eq!(record.kind, TxKind::TokenTransfer);
+        assert_eq!((record.from.as_deref(), record.to.as_deref()), (Some(BOB), Some(ALICE)));
+        assert_eq!(record.value, Some(U256::from(500)));
+        assert_eq!(record.token.as_deref(), Some("0x00000000000000000000000000000000000000c3"));
+        assert_eq!((record.log_index, record.block_number), (Some(1), Some(32)));
+
+        // ERC-721 indexes the token id and carries no data
+        let erc721 = transfer_log("0xcc", 2, vec![TRANSFER_TOPIC.to_string(), topic(BOB), topic(ALICE), word(7)], "0x");
+        let record = record_from_log(&erc721).unwrap();
+        assert_eq!(record.kind, TxKind::NftTransfer);
+        assert_eq!(record.value, Some(U256::from(7)));
+        assert_eq!(record.to.as_deref(), Some(ALICE));
+
+        let approval = transfer_log("0xcc", 3, vec![word(1), topic(BOB), topic(ALICE)], &word(500));
+        assert!(record_from_log(&approval).is_none());
+    }
+
+    #[test]
+    fn tba_call_fills_in_the_zero_value_send() {
+        let tba = "0x00000000000000000000000000000000000000c3";
+        let mut history = History::default();
+        history.insert(TxRecord::sent("0xDD".to_string(), TxKind::TbaSend, ALICE.to_string(), Some(tba.to_string()), Some(U256::zero())));
+
+        let call = transfer_log("0xdd", 0, vec![CALL_EXECUTED_TOPIC.to_string(), topic(BOB)], &format!("{}{}", word(5), &word(0)[2..]));
+        let record = record_from_log(&call).unwrap();
+        assert_eq!(record.kind, TxKind::TbaCall);
+        assert_eq!(record.from.as_deref(), Some(tba));
+        assert!(!history.insert(record.clone()));
+
+        assert_eq!(history.records.len(), 1);
+        assert_eq!(history.records[0].kind, TxKind::TbaSend);
+        assert_eq!(history.records[0].to.as_deref(), Some(BOB));
+        assert_eq!(history.records[0].value, Some(U256::from(5)));
+
+        // A send that already carries a value keeps it
+        let mut history = History::default();
+        history.insert(TxRecord::sent("0xdd".to_string(), TxKind::Send, ALICE.to_string(), Some(tba.to_string()), Some(U256::one())));
+        assert!(!history.insert(record));
+        assert_eq!(history.records[0].value, Some(U256::one()));
+    }
+
+    #[test]
+    fn insert_skips_known_entries() {
+        let mut history = History::default();
+        let log = |i| record_from_log(&transfer_log("0xee", i, vec![TRANSFER_TOPIC.to_string(), topic(BOB), topic(ALICE)], &word(1))).unwrap();
+        assert!(history.insert(log(1)));
+        assert!(!history.insert(log(1)));
+        let mut upper = log(1);
+        upper.hash = "0xEE".to_string();
+        assert!(!history.insert(upper));
+        // Another log of the same transaction is its own entry
+        assert!(history.insert(log(2)));
+        assert_eq!(history.records.len(), 2);
+
+        // Logs of a transaction we sent are covered by its sent record
+        let mut history = History::default();
+        history.insert(TxRecord::sent("0xee".to_string(), TxKind::Send, ALICE.to_string(), None, None));
+        assert!(!history.insert(log(1)));
+        assert!(!history.insert(TxRecord::sent("0xee".to_string(), TxKind::Send, ALICE.to_string(), None, None)));
+        assert_eq!(history.records.len(), 1);
+    }
+
+    #[test]
+    fn backfill_resumes_chunk_by_chunk() {
+        use crate::transport::{set_transport, AnyTransport, MockTransport};
+
+        let network = Network::BaseSepolia;
+        let head = 30_000u64;
+        let nft_block = 25_500u64;
+        let mock = MockTransport::new();
+        mock.respond("eth_blockNumber", json!(format!("0x{:x}", head)));
+        // One NFT arrives at Alice in `nft_block`
+        mock.respond_with("eth_getLogs", move |params: &Value| {
+            let block = |field: &str| u64::from_str_radix(params[0][field].as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+            let incoming = !params[0]["topics"][2].is_null();
+            if incoming && (block("fromBlock")..=block("toBlock")).contains(&nft_block) {
+                let mut log = transfer_log("0xff", 0, vec![TRANSFER_TOPIC.to_string(), topic(BOB), topic(ALICE), word(9)], "0x");
+                log["blockNumber"] = json!(format!("0x{:x}", nft_block));
+                json!([log])
+            } else {
+                json!([])
+            }
+        });
+        set_transport(AnyTransport::Mock(mock.clone()));
+        block_on(update(network, ALICE, |h| h.scanned_to = Some(1_000)));
+
+        let scanned_from = || -> Vec<u64> {
+            let mut starts: Vec<u64> = mock.requests().iter()
+                .filter(|r| r["method"] == "eth_getLogs")
+                .map(|r| u64::from_str_radix(r["params"][0]["fromBlock"].as_str().unwrap().trim_start_matches("0x"), 16).unwrap())
+                .collect();
+            starts.dedup();
+            starts
+        };
+
+        // A run covers at most MAX_CHUNKS_PER_RUN chunks, then saves its progress
+        assert_eq!(block_on(backfill(network, ALICE, None)).unwrap(), 0);
+        let first_run: Vec<u64> = (0..MAX_CHUNKS_PER_RUN as u64).map(|i| 1_001 + i * CHUNK_BLOCKS).collect();
+        assert_eq!(scanned_from(), first_run);
+        assert_eq!(block_on(snapshot(network, ALICE)).scanned_to, Some(21_000));
+
+        // The next one picks up there and reaches the head
+        assert_eq!(block_on(backfill(network, ALICE, None)).unwrap(), 1);
+        assert_eq!(scanned_from()[first_run.len()..], [21_001, 23_001, 25_001, 27_001, 29_001]);
+        let history = block_on(snapshot(network, ALICE));
+        assert_eq!(history.scanned_to, Some(head));
+        assert_eq!(history.records.len(), 1);
+        assert_eq!(history.records[0].kind, TxKind::NftTransfer);
+        assert_eq!(history.records[0].block_number, Some(nft_block));
+
+        // Nothing left to scan
+        let requests = mock.requests().len();
+        assert_eq!(block_on(backfill(network, ALICE, None)).unwrap(), 0);
+        assert!(mock.requests()[requests..].iter().all(|r| r["method"] != "eth_getLogs"));
+    }
+}
@@ -0,0 +1,10 @@
+//! Chain client shared by the wallet and the native debug tools in `src/bin`.
+
+pub mod abi;
+pub mod amount;
+pub mod cache;
+pub mod proof;
+pub mod provider;
+pub mod rpc;
+pub mod storage;
+pub mod transport;
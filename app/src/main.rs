@@ -1,8 +1,18 @@
 use leptos::*;
+// Chain client, also used by the debug tools
+use diamond_wallet_app::{abi, amount, cache, proof, provider, rpc, storage, transport};
 
 mod app;
 mod wallet;
-mod rpc;
+mod crypto;
+mod mnemonic;
+mod vault;
+mod session;
+mod settings;
+mod shamir;
+mod schema;
+mod fees;
+mod history;
 pub mod transactions;
 
 use app::App;
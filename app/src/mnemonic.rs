@@ -0,0 +1,94 @@
+use ethers_core::types::PathOrString;
+use ethers_signers::coins_bip39::{English, Mnemonic};
+use ethers_signers::{LocalWallet, MnemonicBuilder};
+use rand::rngs::OsRng;
+
+/// BIP-44 path of Ethereum account `index` (m/44'/60'/0'/0/i).
+pub fn account_path(index: u32) -> String {
+    format!("m/44'/60'/0'/0/{}", index)
+}
+
+/// Generates a fresh English BIP-39 phrase (12 or 24 words).
+pub fn generate(word_count: usize) -> Result<String, String> {
+    let mnemonic = Mnemonic::<English>::new_with_count(&mut OsRng, word_count).map_err(|e| e.to_string())?;
+    Ok(mnemonic.to_phrase())
+}
+
+/// Trims and lowercases user input and checks words and checksum.
+pub fn normalize(phrase: &str) -> Result<String, String> {
+    let words: Vec<String> = phrase.split_whitespace().map(|w| w.to_lowercase()).collect();
+    if words.len() != 12 && words.len() != 24 {
+        return Err(format!("Expected 12 or 24 words, got {}", words.len()));
+    }
+    let phrase = words.join(" ");
+    Mnemonic::<English>::new_from_phrase(&phrase).map_err(|e| e.to_string())?;
+    Ok(phrase)
+}
+
+/// Derives the signer at `path` from a phrase.
+pub fn derive_wallet(phrase: &str, path: &str) -> Result<LocalWallet, String> {
+    MnemonicBuilder::<English>::default()
+        // Explicit String so the phrase is never treated as a file path
+        .phrase(PathOrString::String(phrase.to_string()))
+        .derivation_path(path)
+        .map_err(|e| e.to_string())?
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers_signers::Signer;
+
+    /// Foundry's default dev phrase
+    const TEST_PHRASE: &str = "test test test test test test test test test test test junk";
+
+    fn address_at(path: &str) -> String {
+        format!("{:?}", derive_wallet(TEST_PHRASE, path).unwrap().address())
+    }
+
+    #[test]
+    fn generates_valid_phrases() {
+        for count in [12, 24] {
+            let phrase = generate(count).unwrap();
+            assert_eq!(phrase.split(' ').count(), count);
+            assert_eq!(normalize(&phrase).unwrap(), phrase);
+        }
+        assert_ne!(generate(12).unwrap(), generate(12).unwrap());
+        assert!(generate(13).is_err());
+    }
+
+    #[test]
+    fn normalizes_case_and_whitespace() {
+        let messy = "  Test TEST test\ttest test test\n\ntest test test test test   JUNK ";
+        assert_eq!(normalize(messy).unwrap(), TEST_PHRASE);
+    }
+
+    #[test]
+    fn rejects_bad_phrases() {
+        // Valid words, wrong checksum
+        assert!(normalize(&["test"; 12].join(" ")).is_err());
+        assert!(normalize("test test test test test test test test test test test notaword").is_err());
+        assert_eq!(normalize("test test junk").unwrap_err(), "Expected 12 or 24 words, got 3");
+    }
+
+    #[test]
+    fn derives_the_known_first_account() {
+        assert_eq!(address_at("m/44'/60'/0'/0/0"), "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+        assert!(derive_wallet(TEST_PHRASE, "not a path").is_err());
+    }
+
+    #[test]
+    fn account_paths_derive_the_anvil_accounts() {
+        let expected = [
+            "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+            "0x70997970c51812dc3a010c7d01b50e0d17dc79c8",
+            "0x3c44cdddb6a900fa2b585dd299e03d12fa4293bc",
+        ];
+        for (index, address) in expected.iter().enumerate() {
+            assert_eq!(account_path(index as u32), format!("m/44'/60'/0'/0/{}", index));
+            assert_eq!(&address_at(&account_path(index as u32)), address, "account {}", index);
+        }
+    }
+}
@@ -0,0 +1,515 @@
+use ethers_core::types::U256;
+use ethers_core::utils::keccak256;
+use ethers_core::utils::rlp::Rlp;
+use serde_json::{json, Value};
+
+use crate::rpc::{Network, RpcError};
+
+/// Blocks behind the head the header is taken from, so endpoints a block
+/// or two apart still agree on it.
+const HEADER_LAG: u64 = 2;
+/// keccak256 of empty code, the code hash of accounts without code
+const EMPTY_CODE_HASH: &str = "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470";
+
+/// A block header whose state root we trust: agreed on by a majority of
+/// endpoints, or served by the network's only endpoint.
+#[derive(Clone, Copy, Debug)]
+pub struct TrustedHeader {
+    pub number: u64,
+    state_root: [u8; 32],
+}
+
+/// An account as proven against a `TrustedHeader`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AccountState {
+    pub nonce: u64,
+    pub balance: U256,
+    pub storage_root: [u8; 32],
+    pub code_hash: [u8; 32],
+}
+
+fn unverified(reason: impl Into<String>) -> RpcError {
+    RpcError::Unverified(reason.into())
+}
+
+fn hex_bytes(val: &Value) -> Result<Vec<u8>, RpcError> {
+    let hex = val.as_str().ok_or_else(|| RpcError::Decode(format!("expected hex data, got {}", val)))?;
+    alloy_primitives::hex::decode(hex.trim_start_matches("0x")).map_err(|e| RpcError::Decode(e.to_string()))
+}
+
+fn word(bytes: &[u8]) -> Result<[u8; 32], RpcError> {
+    bytes.try_into().map_err(|_| unverified(format!("expected 32 bytes, got {}", bytes.len())))
+}
+
+/// Asks every endpoint for the header `HEADER_LAG` blocks behind the head.
+pub async fn trusted_header(network: Network) -> Result<TrustedHeader, RpcError> {
+    let number = crate::rpc::get_block_number(network).await?.saturating_sub(HEADER_LAG);
+    let body = json!({
+        "jsonrpc": "2.0",
+        "method": "eth_getBlockByNumber",
+        "params": [format!("0x{:x}", number), false],
+        "id": 1
+    });
+    let endpoints = network.rpc_urls().len();
+    let votes: Vec<Value> = crate::provider::post_many(network, &body, endpoints).await
+        .into_iter()
+        .filter_map(|r| r.and_then(crate::rpc::into_result).ok())
+        .filter(|header| !header.is_null())
+        .map(|header| json!([header["hash"], header["stateRoot"]]))
+        .collect();
+    if votes.is_empty() {
+        return Err(unverified("no endpoint returned the header"));
+    }
+    let winner = elect(endpoints, &votes).map_err(|agreeing| {
+        unverified(format!("endpoints disagree on block {} ({} of {})", number, agreeing, votes.len()))
+    })?;
+    Ok(TrustedHeader { number, state_root: word(&hex_bytes(&winner[1])?)? })
+}
+
+/// The answer of `votes` (from `endpoints` asked) to trust, or how many
+/// agreed at best. A lone endpoint is trusted by configuration; otherwise
+/// two at least, and most of those that answered.
+fn elect(endpoints: usize, votes: &[Value]) -> Result<&Value, usize> {
+    let count_of = |v: &Value| votes.iter().filter(|w| *w == v).count();
+    let winner = votes.iter().max_by_key(|v| count_of(v)).ok_or(0usize)?;
+    let agreeing = count_of(winner);
+    if endpoints > 1 && (agreeing < 2 || agreeing * 2 <= votes.len()) {
+        return Err(agreeing);
+    }
+    Ok(winner)
+}
+
+/// Where a trie node points next.
+enum Child {
+    Hash([u8; 32]),
+    /// Nodes shorter than 32 bytes are embedded in their parent
+    Inline(Vec<u8>),
+    Empty,
+}
+
+fn child(item: Rlp) -> Result<Child, RpcError> {
+    if item.is_list() {
+        return Ok(Child::Inline(item.as_raw().to_vec()));
+    }
+    let data = item.data().map_err(|e| unverified(e.to_string()))?;
+    match data.len() {
+        0 => Ok(Child::Empty),
+        32 => Ok(Child::Hash(word(data)?)),
+        n => Err(unverified(format!("bad child reference of {} bytes", n))),
+    }
+}
+
+/// Hex-prefix encoded path of a leaf or extension node: nibbles and whether it is a leaf.
+fn decode_path(encoded: &[u8]) -> Result<(Vec<u8>, bool), RpcError> {
+    let first = *encoded.first().ok_or(unverified("empty node path"))?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return Err(unverified(format!("bad path flag {}", flag)));
+    }
+    let mut nibbles = Vec::with_capacity(encoded.len() * 2);
+    // Odd paths keep their first nibble next to the flag
+    if flag & 1 == 1 {
+        nibbles.push(first & 0x0f);
+    }
+    for b in &encoded[1..] {
+        nibbles.extend([b >> 4, b & 0x0f]);
+    }
+    Ok((nibbles, flag >= 2))
+}
+
+/// Walks `proof` from `root` along keccak256(`key`) and returns the value
+/// stored there, or `None` when the proof shows there is none.
+pub fn verify_proof(root: [u8; 32], key: &[u8], proof: &[Vec<u8>]) -> Result<Option<Vec<u8>>, RpcError> {
+    let path: Vec<u8> = keccak256(key).iter().flat_map(|b| [b >> 4, b & 0x0f]).collect();
+    let mut nodes = proof.iter();
+    let mut next = Child::Hash(root);
+    let mut pos = 0;
+    let value = loop {
+        let node = match next {
+            Child::Hash(hash) => {
+                let node = nodes.next().ok_or(unverified("proof ends early"))?;
+                if keccak256(node) != hash {
+                    return Err(unverified("proof node does not match its hash"));
+                }
+                node.clone()
+            },
+            Child::Inline(node) => node,
+            Child::Empty => break None,
+        };
+        let node = Rlp::new(&node);
+        let at = |i: usize| node.at(i).map_err(|e| unverified(e.to_string()));
+        match node.item_count().map_err(|e| unverified(e.to_string()))? {
+            // Branch: one child per nibble, plus a value for keys ending here
+            17 => {
+                let nibble = *path.get(pos).ok_or(unverified("key ends inside a branch"))?;
+                next = child(at(nibble as usize)?)?;
+                pos += 1;
+            },
+            // Extension or leaf
+            2 => {
+                let (segment, is_leaf) = decode_path(at(0)?.data().map_err(|e| unverified(e.to_string()))?)?;
+                let rest = &path[pos..];
+                if is_leaf {
+                    if rest != segment.as_slice() {
+                        break None;
+                    }
+                    break Some(at(1)?.data().map_err(|e| unverified(e.to_string()))?.to_vec());
+                }
+                if !rest.starts_with(&segment) {
+                    break None;
+                }
+                pos += segment.len();
+                next = child(at(1)?)?;
+            },
+            n => return Err(unverified(format!("trie node with {} items", n))),
+        }
+    };
+    if nodes.next().is_some() {
+        return Err(unverified("proof has extra nodes"));
+    }
+    Ok(value)
+}
+
+fn decode_account(rlp: &[u8]) -> Result<AccountState, RpcError> {
+    let account = Rlp::new(rlp);
+    let field = |i: usize| account.at(i).and_then(|f| f.data().map(<[u8]>::to_vec)).map_err(|e| unverified(e.to_string()));
+    Ok(AccountState {
+        nonce: U256::from_big_endian(&field(0)?).low_u64(),
+        balance: U256::from_big_endian(&field(1)?),
+        storage_root: word(&field(2)?)?,
+        code_hash: word(&field(3)?)?,
+    })
+}
+
+/// `address` as of `header`, from `eth_getProof` checked against its state
+/// root. Accounts proven absent come back empty.
+pub async fn get_account(network: Network, address: &str, header: &TrustedHeader) -> Result<AccountState, RpcError> {
+    let address_bytes = alloy_primitives::hex::decode(address.trim_start_matches("0x")).map_err(|e| RpcError::Decode(e.to_string()))?;
+    let response = crate::rpc::eth_call(network, "eth_getProof", json!([address, [], format!("0x{:x}", header.number)])).await?;
+    let proof = response["accountProof"].as_array().ok_or(RpcError::Decode("eth_getProof without accountProof".to_string()))?
+        .iter()
+        .map(hex_bytes)
+        .collect::<Result<Vec<_>, _>>()?;
+    match verify_proof(header.state_root, &address_bytes, &proof)? {
+        Some(account) => decode_account(&account),
+        None => Ok(AccountState {
+            nonce: 0,
+            balance: U256::zero(),
+            storage_root: keccak256([0x80]),
+            code_hash: word(&alloy_primitives::hex::decode(EMPTY_CODE_HASH).unwrap_or_default())?,
+        }),
+    }
+}
+
+/// Code at `address` as of `header`, checked against the proven code hash.
+pub async fn get_code(network: Network, address: &str, header: &TrustedHeader) -> Result<Vec<u8>, RpcError> {
+    let account = get_account(network, address, header).await?;
+    let code = hex_bytes(&crate::rpc::eth_call(network, "eth_getCode", json!([address, format!("0x{:x}", header.number)])).await?)?;
+    if keccak256(&code) != account.code_hash {
+        return Err(unverified("code does not match the proven code hash"));
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{set_transport, AnyTransport, MockTransport};
+    use ethers_core::types::Address;
+    use ethers_core::utils::rlp::RlpStream;
+    use futures::executor::block_on;
+
+    // Tries are built the way a node stores state: keys hashed with
+    // keccak256, hex-prefix paths, and children under 32 bytes inlined.
+
+    fn path_of(address: &Address) -> Vec<u8> {
+        keccak256(address).iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+    }
+
+    fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+        let flag = if leaf { 2 } else { 0 } + (nibbles.len() % 2) as u8;
+        let mut encoded = vec![flag << 4];
+        let mut rest = nibbles;
+        if flag & 1 == 1 {
+            encoded[0] |= nibbles[0];
+            rest = &nibbles[1..];
+        }
+        encoded.extend(rest.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+        encoded
+    }
+
+    fn append_ref(stream: &mut RlpStream, node: &[u8]) {
+        if node.len() < 32 {
+            stream.append_raw(node, 1);
+        } else {
+            stream.append(&keccak256(node).to_vec());
+        }
+    }
+
+    fn leaf(nibbles: &[u8], value: &[u8]) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix(nibbles, true)).append(&value.to_vec());
+        stream.out().to_vec()
+    }
+
+    fn extension(nibbles: &[u8], child: &[u8]) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix(nibbles, false));
+        append_ref(&mut stream, child);
+        stream.out().to_vec()
+    }
+
+    fn branch(children: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(17);
+        for nibble in 0..16 {
+            match children.iter().find(|(n, _)| *n == nibble) {
+                Some((_, child)) => append_ref(&mut stream, child),
+                None => { stream.append_empty_data(); },
+            }
+        }
+        stream.append_empty_data();
+        stream.out().to_vec()
+    }
+
+    fn account(nonce: u64, balance: U256) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&nonce)
+            .append(&balance)
+            .append(&keccak256([0x80]).to_vec())
+            .append(&alloy_primitives::hex::decode(EMPTY_CODE_HASH).unwrap());
+        stream.out().to_vec()
+    }
+
+    /// The first addresses from 1 up whose hashed paths satisfy `matches`.
+    fn addresses(n: usize, matches: impl Fn(&[Vec<u8>], &[u8]) -> bool) -> Vec<Address> {
+        let mut found: Vec<Address> = Vec::new();
+        let mut paths: Vec<Vec<u8>> = Vec::new();
+        for i in 1.. {
+            let address = Address::from_low_u64_be(i);
+            let path = path_of(&address);
+            if matches(&paths, &path) {
+                found.push(address);
+                paths.push(path);
+                if found.len() == n {
+                    break;
+                }
+            }
+        }
+        found
+    }
+
+    /// Root branch over accounts `a` and `b`, which start with different nibbles.
+    struct TwoAccounts {
+        a: Address,
+        b: Address,
+        root: [u8; 32],
+        branch: Vec<u8>,
+        leaf_a: Vec<u8>,
+        leaf_b: Vec<u8>,
+    }
+
+    fn two_accounts() -> TwoAccounts {
+        let found = addresses(2, |paths, path| paths.iter().all(|p| p[0] != path[0]));
+        let (a, b) = (found[0], found[1]);
+        let (path_a, path_b) = (path_of(&a), path_of(&b));
+        let leaf_a = leaf(&path_a[1..], &account(3, U256::exp10(18)));
+        let leaf_b = leaf(&path_b[1..], &account(0, U256::from(7)));
+        let branch = branch(&[(path_a[0], &leaf_a), (path_b[0], &leaf_b)]);
+        TwoAccounts { a, b, root: keccak256(&branch), branch, leaf_a, leaf_b }
+    }
+
+    fn verify(root: [u8; 32], address: &Address, proof: &[Vec<u8>]) -> Result<Option<Vec<u8>>, RpcError> {
+        verify_proof(root, address.as_bytes(), proof)
+    }
+
+    #[test]
+    fn proves_an_existing_account() {
+        let t = two_accounts();
+        let value = verify(t.root, &t.a, &[t.branch.clone(), t.leaf_a.clone()]).unwrap().unwrap();
+        let state = decode_account(&value).unwrap();
+        assert_eq!(state.nonce, 3);
+        assert_eq!(state.balance, U256::exp10(18));
+        assert_eq!(state.storage_root, keccak256([0x80]));
+
+        let value = verify(t.root, &t.b, &[t.branch.clone(), t.leaf_b.clone()]).unwrap().unwrap();
+        assert_eq!(decode_account(&value).unwrap().balance, U256::from(7));
+    }
+
+    #[test]
+    fn proves_absence_through_a_divergent_leaf() {
+        let t = two_accounts();
+        let first = path_of(&t.a)[0];
+        // Shares the first nibble with `a`, so the walk ends at a's leaf
+        let absent = addresses(1, |_, path| path[0] == first && path != path_of(&t.a).as_slice())[0];
+        assert_eq!(verify(t.root, &absent, &[t.branch.clone(), t.leaf_a.clone()]), Ok(None));
+    }
+
+    #[test]
+    fn proves_absence_through_an_empty_branch_slot() {
+        let t = two_accounts();
+        let taken = [path_of(&t.a)[0], path_of(&t.b)[0]];
+        let absent = addresses(1, |_, path| !taken.contains(&path[0]))[0];
+        assert_eq!(verify(t.root, &absent, std::slice::from_ref(&t.branch)), Ok(None));
+    }
+
+    #[test]
+    fn proves_absence_through_an_extension() {
+        // Two accounts sharing their first nibble sit under an extension
+        let found = addresses(2, |paths, path| paths.iter().all(|p| p[0] == path[0] && p[1] != path[1]));
+        let (path_x, path_y) = (path_of(&found[0]), path_of(&found[1]));
+        let leaf_x = leaf(&path_x[2..], &account(1, U256::one()));
+        let leaf_y = leaf(&path_y[2..], &account(2, U256::one()));
+        let inner = branch(&[(path_x[1], &leaf_x), (path_y[1], &leaf_y)]);
+        let ext = extension(&path_x[..1], &inner);
+        let root = keccak256(&ext);
+
+        let value = verify(root, &found[0], &[ext.clone(), inner.clone(), leaf_x]).unwrap().unwrap();
+        assert_eq!(decode_account(&value).unwrap().nonce, 1);
+
+        let absent = addresses(1, |_, path| path[0] != path_x[0])[0];
+        assert_eq!(verify(root, &absent, std::slice::from_ref(&ext)), Ok(None));
+    }
+
+    #[test]
+    fn follows_inline_children() {
+        // A leaf one nibble from the end is short enough to live in its parent
+        let address = Address::from_low_u64_be(1);
+        let path = path_of(&address);
+        let short = leaf(&path[63..], &[0x2a]);
+        let other = leaf(&[(path[63] + 1) % 16], &[0x2b]);
+        assert!(short.len() < 32);
+        let inner = branch(&[(path[62], &short), ((path[62] + 1) % 16, &other)]);
+        // So is the branch over two such leaves
+        assert!(inner.len() < 32);
+        let ext = extension(&path[..62], &inner);
+        let root = keccak256(&ext);
+
+        // Inline nodes are part of their parent, not proof nodes of their own
+        assert_eq!(verify(root, &address, std::slice::from_ref(&ext)), Ok(Some(vec![0x2a])));
+        assert_eq!(
+            verify(root, &address, &[ext, inner]),
+            Err(RpcError::Unverified("proof has extra nodes".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_a_tampered_node() {
+        let t = two_accounts();
+        let mut leaf_a = t.leaf_a.clone();
+        *leaf_a.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            verify(t.root, &t.a, &[t.branch.clone(), leaf_a]),
+            Err(RpcError::Unverified("proof node does not match its hash".to_string()))
+        );
+
+        // A consistent proof for a different root is no better
+        let mut branch = t.branch.clone();
+        branch[5] ^= 1;
+        assert!(matches!(verify(t.root, &t.a, &[branch, t.leaf_a.clone()]), Err(RpcError::Unverified(_))));
+    }
+
+    #[test]
+    fn rejects_a_truncated_proof() {
+        let t = two_accounts();
+        assert_eq!(
+            verify(t.root, &t.a, std::slice::from_ref(&t.branch)),
+            Err(RpcError::Unverified("proof ends early".to_string()))
+        );
+        assert_eq!(verify(t.root, &t.a, &[]), Err(RpcError::Unverified("proof ends early".to_string())));
+    }
+
+    #[test]
+    fn rejects_extra_nodes() {
+        let t = two_accounts();
+        assert_eq!(
+            verify(t.root, &t.a, &[t.branch.clone(), t.leaf_a.clone(), t.leaf_b.clone()]),
+            Err(RpcError::Unverified("proof has extra nodes".to_string()))
+        );
+    }
+
+    #[test]
+    fn decodes_hex_prefix_paths() {
+        assert_eq!(decode_path(&[0x00, 0x12]).unwrap(), (vec![1, 2], false));
+        assert_eq!(decode_path(&[0x11, 0x23]).unwrap(), (vec![1, 2, 3], false));
+        assert_eq!(decode_path(&[0x20]).unwrap(), (vec![], true));
+        assert_eq!(decode_path(&[0x3f]).unwrap(), (vec![0xf], true));
+        assert!(decode_path(&[0x40]).is_err());
+        assert!(decode_path(&[]).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_accounts() {
+        assert!(decode_account(&[0xc0]).is_err());
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&1u64).append(&1u64).append(&vec![0u8; 31]).append(&vec![0u8; 32]);
+        assert!(decode_account(&stream.out()).is_err());
+    }
+
+    #[test]
+    fn lone_endpoint_is_trusted() {
+        assert_eq!(elect(1, &[json!("a")]), Ok(&json!("a")));
+    }
+
+    #[test]
+    fn two_of_three_agree() {
+        let votes = [json!("a"), json!("b"), json!("a")];
+        assert_eq!(elect(3, &votes), Ok(&json!("a")));
+        // Two agreeing while the third did not answer is still a majority
+        assert_eq!(elect(3, &[json!("a"), json!("a")]), Ok(&json!("a")));
+    }
+
+    #[test]
+    fn split_votes_are_rejected() {
+        assert_eq!(elect(2, &[json!("a"), json!("b")]), Err(1));
+        assert_eq!(elect(4, &[json!("a"), json!("a"), json!("b"), json!("b")]), Err(2));
+        // One answer out of several endpoints is not enough
+        assert_eq!(elect(3, &[json!("a")]), Err(1));
+        assert_eq!(elect(3, &[]), Err(0));
+    }
+
+    fn serve_header(mock: &MockTransport, url: Option<&str>, state_root: [u8; 32]) {
+        let header = json!({ "hash": format!("0x{}", alloy_primitives::hex::encode(keccak256(state_root))), "stateRoot": format!("0x{}", alloy_primitives::hex::encode(state_root)) });
+        match url {
+            Some(url) => mock.respond_at(url, "eth_getBlockByNumber", header),
+            None => mock.respond("eth_getBlockByNumber", header),
+        };
+    }
+
+    #[test]
+    fn get_account_checks_the_proof_against_the_header() {
+        let t = two_accounts();
+        let mock = MockTransport::new();
+        set_transport(AnyTransport::Mock(mock.clone()));
+        mock.respond("eth_blockNumber", json!("0x10"));
+        serve_header(&mock, None, t.root);
+        let proof: Vec<String> = [&t.branch, &t.leaf_a].iter().map(|n| format!("0x{}", alloy_primitives::hex::encode(n))).collect();
+        mock.respond("eth_getProof", json!({ "accountProof": proof }));
+
+        let header = block_on(trusted_header(Network::BaseSepolia)).unwrap();
+        assert_eq!(header.number, 0x0e);
+        let state = block_on(get_account(Network::BaseSepolia, &format!("{:?}", t.a), &header)).unwrap();
+        assert_eq!(state.balance, U256::exp10(18));
+        assert_eq!(mock.requests().last().unwrap()["params"], json!([format!("{:?}", t.a), [], "0xe"]));
+
+        // The same proof does not vouch for another account
+        let err = block_on(get_account(Network::BaseSepolia, &format!("{:?}", t.b), &header)).unwrap_err();
+        assert!(matches!(err, RpcError::Unverified(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn trusted_header_needs_endpoints_to_agree() {
+        let mock = MockTransport::new();
+        set_transport(AnyTransport::Mock(mock.clone()));
+        mock.respond("eth_blockNumber", json!("0x10"));
+        let urls = Network::BaseSepolia.rpc_urls();
+        serve_header(&mock, Some(urls[0]), [1; 32]);
+        serve_header(&mock, Some(urls[1]), [2; 32]);
+
+        let err = block_on(trusted_header(Network::BaseSepolia)).unwrap_err();
+        assert_eq!(err, RpcError::Unverified("endpoints disagree on block 14 (1 of 2)".to_string()));
+
+        // Anvil's single endpoint is trusted alone
+        serve_header(&mock, Some(Network::Anvil.rpc_url()), [3; 32]);
+        assert_eq!(block_on(trusted_header(Network::Anvil)).unwrap().state_root, [3; 32]);
+    }
+}
@@ -0,0 +1,305 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use crate::rpc::{Network, RpcError};
+use crate::transport::Transport;
+
+// Latency assumed for endpoints we have not measured yet, so a slow primary
+// lets a fallback get a turn
+const UNMEASURED_LATENCY_MS: f64 = 300.0;
+const FAILURE_PENALTY_MS: f64 = 500.0;
+const RATE_LIMIT_COOLDOWN_MS: f64 = 30_000.0;
+const FAILURE_COOLDOWN_MS: f64 = 10_000.0;
+const MAX_COOLDOWN_MS: f64 = 60_000.0;
+
+/// What we have observed of one RPC endpoint this session.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct EndpointHealth {
+    /// Moving average of successful round trips
+    pub latency_ms: Option<f64>,
+    pub successes: u32,
+    pub failures: u32,
+    consecutive_failures: u32,
+    cooldown_until: f64,
+}
+
+impl EndpointHealth {
+    pub fn cooling_down(&self, now: f64) -> bool {
+        now < self.cooldown_until
+    }
+
+    /// Lower is better.
+    fn score(&self) -> f64 {
+        self.latency_ms.unwrap_or(UNMEASURED_LATENCY_MS) + FAILURE_PENALTY_MS * self.consecutive_failures as f64
+    }
+}
+
+thread_local! {
+    static HEALTH: RefCell<HashMap<&'static str, EndpointHealth>> = RefCell::new(HashMap::new());
+    static READ_QUORUM: Cell<usize> = const { Cell::new(1) };
+    /// `eth_chainId` of each endpoint, asked once
+    static CHAIN_IDS: RefCell<HashMap<&'static str, u64>> = RefCell::new(HashMap::new());
+}
+
+fn now() -> f64 {
+    crate::transport::now_ms()
+}
+
+fn health(url: &'static str) -> EndpointHealth {
+    HEALTH.with(|h| h.borrow().get(url).copied().unwrap_or_default())
+}
+
+fn update(url: &'static str, f: impl FnOnce(&mut EndpointHealth)) {
+    HEALTH.with(|h| f(h.borrow_mut().entry(url).or_default()));
+}
+
+fn record_success(url: &'static str, latency_ms: f64) {
+    update(url, |h| {
+        h.latency_ms = Some(match h.latency_ms {
+            Some(avg) => avg * 0.7 + latency_ms * 0.3,
+            None => latency_ms,
+        });
+        h.successes += 1;
+        h.consecutive_failures = 0;
+        h.cooldown_until = 0.0;
+    });
+}
+
+fn record_failure(url: &'static str, err: &RpcError) {
+    update(url, |h| {
+        h.failures += 1;
+        h.consecutive_failures += 1;
+        let cooldown = if err.is_rate_limited() {
+            RATE_LIMIT_COOLDOWN_MS
+        } else {
+            (FAILURE_COOLDOWN_MS * h.consecutive_failures as f64).min(MAX_COOLDOWN_MS)
+        };
+        h.cooldown_until = now() + cooldown;
+    });
+}
+
+/// Errors that say nothing about the request itself, so another endpoint
+/// (or a later attempt) may succeed. A non-idempotent request is only
+/// repeated when it was provably rejected before processing: after a
+/// transport error or 5xx it may already have reached the node.
+pub fn is_retryable(err: &RpcError, idempotent: bool) -> bool {
+    if err.is_rate_limited() {
+        return true;
+    }
+    idempotent && match err {
+        RpcError::Transport(_) => true,
+        RpcError::Http { status, .. } => *status >= 500,
+        _ => false,
+    }
+}
+
+fn retry_after_ms(err: &RpcError) -> Option<u64> {
+    match err {
+        RpcError::Http { retry_after_ms, .. } => *retry_after_ms,
+        _ => None,
+    }
+}
+
+fn known_chain(url: &'static str) -> Option<u64> {
+    CHAIN_IDS.with(|c| c.borrow().get(url).copied())
+}
+
+/// Endpoints of `network`, healthiest first. Endpoints cooling down after a
+/// failure go last but are still tried when everything else fails. Endpoints
+/// known to serve another chain are never used.
+pub fn ranked(network: Network) -> Vec<&'static str> {
+    let now = now();
+    let mut urls = network.rpc_urls().to_vec();
+    urls.retain(|url| known_chain(url).is_none_or(|id| id == network.chain_id()));
+    // Stable sort keeps the configured order among equals
+    urls.sort_by(|a, b| {
+        let (ha, hb) = (health(a), health(b));
+        ha.cooling_down(now).cmp(&hb.cooling_down(now))
+            .then(ha.score().total_cmp(&hb.score()))
+    });
+    urls
+}
+
+/// Per-endpoint health of `network` in configured order, for display.
+pub fn snapshot(network: Network) -> Vec<(&'static str, EndpointHealth)> {
+    network.rpc_urls().iter().map(|url| (*url, health(url))).collect()
+}
+
+/// How many endpoints must agree on critical reads (nonce, balance). 1 = off.
+pub fn set_read_quorum(n: usize) {
+    READ_QUORUM.with(|q| q.set(n.max(1)));
+}
+
+pub fn read_quorum() -> usize {
+    READ_QUORUM.with(|q| q.get())
+}
+
+async fn post_to(url: &'static str, body: &serde_json::Value) -> Result<serde_json::Value, RpcError> {
+    let start = now();
+    let result = crate::transport::current().post(url, body).await.and_then(|json| {
+        // Some providers rate limit inside a 200 response
+        if json["error"]["code"].as_i64() == Some(-32005) {
+            return Err(crate::rpc::into_result(json).unwrap_err());
+        }
+        Ok(json)
+    });
+
+    match &result {
+        Ok(_) => record_success(url, now() - start),
+        Err(e) if is_retryable(e, true) => record_failure(url, e),
+        // The endpoint answered; the request itself was bad
+        Err(_) => record_success(url, now() - start),
+    }
+    result
+}
+
+async fn chain_id_of(url: &'static str) -> Result<u64, RpcError> {
+    if let Some(id) = known_chain(url) {
+        return Ok(id);
+    }
+    let body = serde_json::json!({ "jsonrpc": "2.0", "method": "eth_chainId", "params": [], "id": 0 });
+    let id = crate::rpc::decode_u64(crate::rpc::into_result(post_to(url, &body).await?)?)?;
+    CHAIN_IDS.with(|c| c.borrow_mut().insert(url, id));
+    Ok(id)
+}
+
+/// Checks that every endpoint of `network` serves its chain, asking each
+/// one only once. Unreachable endpoints are not held against the network,
+/// but at least one endpoint must confirm it.
+pub async fn verify_chain(network: Network) -> Result<(), RpcError> {
+    let urls = network.rpc_urls();
+    let answers = futures::future::join_all(urls.iter().map(|url| chain_id_of(url))).await;
+    let mut confirmed = false;
+    let mut last_err = RpcError::Transport("no endpoints configured".to_string());
+    for (url, answer) in urls.iter().zip(answers) {
+        match answer {
+            Ok(id) if id == network.chain_id() => confirmed = true,
+            Ok(actual) => return Err(RpcError::WrongChain { url: url.to_string(), expected: network.chain_id(), actual }),
+            Err(e) => last_err = e,
+        }
+    }
+    if confirmed { Ok(()) } else { Err(last_err) }
+}
+
+/// POSTs to the healthiest endpoint, failing over to the next one on
+/// retryable errors. Once every endpoint failed, waits out the network's
+/// backoff and starts over, up to its `max_attempts`.
+pub async fn post(network: Network, body: &serde_json::Value, idempotent: bool) -> Result<serde_json::Value, RpcError> {
+    let policy = network.retry_policy();
+    let mut last_err = RpcError::Transport("no endpoints configured".to_string());
+    for attempt in 0..policy.max_attempts.max(1) {
+        if attempt > 0 {
+            let delay = policy.delay_ms(attempt - 1, retry_after_ms(&last_err));
+            crate::transport::sleep(delay as u32).await;
+        }
+        for url in ranked(network) {
+            match post_to(url, body).await {
+                Err(e) if is_retryable(&e, idempotent) => last_err = e,
+                result => return result,
+            }
+        }
+    }
+    Err(last_err)
+}
+
+/// POSTs the same body to the `n` healthiest endpoints concurrently.
+pub async fn post_many(network: Network, body: &serde_json::Value, n: usize) -> Vec<Result<serde_json::Value, RpcError>> {
+    let urls: Vec<_> = ranked(network).into_iter().take(n).collect();
+    futures::future::join_all(urls.into_iter().map(|url| post_to(url, body))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{set_transport, AnyTransport, MockTransport};
+    use futures::executor::block_on;
+    use serde_json::json;
+
+    fn mock() -> MockTransport {
+        let mock = MockTransport::new();
+        set_transport(AnyTransport::Mock(mock.clone()));
+        mock
+    }
+
+    fn request(method: &str) -> serde_json::Value {
+        json!({ "jsonrpc": "2.0", "method": method, "params": [], "id": 1 })
+    }
+
+    #[test]
+    fn reads_fail_over_to_the_next_endpoint() {
+        let mock = mock();
+        let urls = Network::BaseSepolia.rpc_urls();
+        mock.unreachable(urls[0]).respond("eth_blockNumber", json!("0x10"));
+
+        let reply = block_on(post(Network::BaseSepolia, &request("eth_blockNumber"), true)).unwrap();
+        assert_eq!(reply["result"], "0x10");
+        assert_eq!(mock.requests_to(urls[0]).len(), 1);
+        assert_eq!(mock.requests_to(urls[1]).len(), 1);
+
+        // The failed endpoint now ranks last, so the next read skips it
+        assert_eq!(ranked(Network::BaseSepolia), [urls[1], urls[0]]);
+        block_on(post(Network::BaseSepolia, &request("eth_blockNumber"), true)).unwrap();
+        assert_eq!(mock.requests_to(urls[0]).len(), 1);
+        assert_eq!(health(urls[0]).failures, 1);
+        assert_eq!(health(urls[1]).successes, 2);
+    }
+
+    #[test]
+    fn node_errors_are_not_retried() {
+        let mock = mock();
+        mock.fail("eth_call", 3, "execution reverted");
+
+        let reply = block_on(post(Network::BaseSepolia, &request("eth_call"), true)).unwrap();
+        assert_eq!(reply["error"]["code"], 3);
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[test]
+    fn sends_are_not_repeated_after_a_transport_error() {
+        let mock = mock();
+        let urls = Network::BaseSepolia.rpc_urls();
+        mock.unreachable(urls[0]).respond("eth_sendRawTransaction", json!("0xabc"));
+
+        let err = block_on(crate::rpc::send_raw_transaction("0x02f8", Network::BaseSepolia)).unwrap_err();
+        assert!(matches!(err, RpcError::Transport(_)));
+        // It may have reached the first node, so no other endpoint sees it
+        assert_eq!(mock.requests_to(urls[0]).len(), 1);
+        assert!(mock.requests_to(urls[1]).is_empty());
+    }
+
+    #[test]
+    fn rate_limited_sends_are_retried_elsewhere() {
+        let mock = mock();
+        let urls = Network::BaseSepolia.rpc_urls();
+        mock.fail_at(urls[0], "eth_sendRawTransaction", -32005, "limit exceeded")
+            .respond("eth_sendRawTransaction", json!("0xabc"));
+
+        let hash = block_on(crate::rpc::send_raw_transaction("0x02f8", Network::BaseSepolia)).unwrap();
+        assert_eq!(hash, "0xabc");
+        assert_eq!(mock.requests_to(urls[1]).len(), 1);
+        assert!(health(urls[0]).cooling_down(now()));
+    }
+
+    #[tokio::test]
+    async fn retries_stop_after_max_attempts() {
+        let mock = mock();
+        let url = Network::Anvil.rpc_url();
+        mock.unreachable(url);
+
+        let err = post(Network::Anvil, &request("eth_blockNumber"), true).await.unwrap_err();
+        assert!(matches!(err, RpcError::Transport(_)));
+        assert_eq!(mock.requests().len() as u32, Network::Anvil.retry_policy().max_attempts);
+    }
+
+    #[test]
+    fn wrong_chain_endpoints_are_dropped() {
+        let mock = mock();
+        let urls = Network::BaseSepolia.rpc_urls();
+        mock.respond_at(urls[0], "eth_chainId", json!("0x1"))
+            .respond_at(urls[1], "eth_chainId", json!("0x14a34"));
+
+        let err = block_on(verify_chain(Network::BaseSepolia)).unwrap_err();
+        assert_eq!(err, RpcError::WrongChain { url: urls[0].to_string(), expected: 84532, actual: 1 });
+        assert_eq!(ranked(Network::BaseSepolia), [urls[1]]);
+    }
+}
@@ -1,91 +1,763 @@
-use gloo_net::http::Request;
+use std::cell::RefCell;
+use std::collections::HashMap;
 
+use futures::channel::oneshot;
+use futures::{SinkExt, StreamExt};
+use gloo_net::websocket::{futures::WebSocket, Message};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 // use leptos::*;
 
+use crate::amount::Amount;
+
+/// Chains the wallet can talk to. Everything chain-specific hangs off this.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Network {
+    #[default]
     BaseSepolia,
+    BaseMainnet,
+    EthSepolia,
+    /// Local `anvil` devnet on the default port
+    Anvil,
+}
+
+/// Deployed addresses on one network; None where not deployed.
+#[derive(Clone, Copy, Default)]
+pub struct Contracts {
+    pub erc6551_registry: Option<&'static str>,
+    pub tba_implementation: Option<&'static str>,
+    pub identity_nft: Option<&'static str>,
+    /// Account that sweeps and resets send funds to
+    pub deployer: Option<&'static str>,
+}
+
+/// How failed RPC requests are retried on a network.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Total tries per request, including the first
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
 
+impl RetryPolicy {
+    /// Jittered exponential backoff before retry number `retry` (0-based).
+    /// A server's Retry-After wins when it asks for longer.
+    pub fn delay_ms(&self, retry: u32, retry_after_ms: Option<u64>) -> u64 {
+        let exp = self.base_delay_ms.saturating_mul(1 << retry.min(16)).min(self.max_delay_ms);
+        // "Equal jitter": half fixed, half random, so clients spread out
+        let jittered = exp / 2 + (rand::random::<f64>() * (exp / 2) as f64) as u64;
+        jittered.max(retry_after_ms.unwrap_or(0).min(self.max_delay_ms))
+    }
+}
+
+/// Whether a request can be repeated without side effects. Sends are never
+/// repeated automatically; the sender checks by hash instead.
+pub fn is_idempotent(method: &str) -> bool {
+    !matches!(method, "eth_sendRawTransaction" | "eth_sendTransaction")
 }
 
+// Canonical ERC-6551 registry (same address on every chain it is deployed to)
+const ERC6551_REGISTRY: &str = "0x000000006551c19487814612e58FE06813775758";
+
 impl Network {
+    pub const ALL: [Network; 4] = [Network::BaseSepolia, Network::BaseMainnet, Network::EthSepolia, Network::Anvil];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::BaseSepolia => "Base Sepolia",
+            Network::BaseMainnet => "Base",
+            Network::EthSepolia => "Sepolia",
+            Network::Anvil => "Anvil",
+        }
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        match self {
+            Network::BaseSepolia => 84532,
+            Network::BaseMainnet => 8453,
+            Network::EthSepolia => 11155111,
+            Network::Anvil => 31337,
+        }
+    }
+
+    pub fn is_testnet(&self) -> bool {
+        !matches!(self, Network::BaseMainnet)
+    }
+
+    /// Local dev chains, which start from fresh state on every node restart.
+    pub fn is_ephemeral(&self) -> bool {
+        matches!(self, Network::Anvil)
+    }
+
+    /// Public endpoints, preferred first.
+    pub fn rpc_urls(&self) -> &'static [&'static str] {
+        match self {
+            Network::BaseSepolia => &["https://sepolia.base.org", "https://base-sepolia-rpc.publicnode.com"],
+            Network::BaseMainnet => &["https://mainnet.base.org", "https://base-rpc.publicnode.com"],
+            Network::EthSepolia => &["https://ethereum-sepolia-rpc.publicnode.com", "https://rpc.sepolia.org"],
+            Network::Anvil => &["http://127.0.0.1:8545"],
+        }
+    }
+
+    /// WebSocket endpoint for `eth_subscribe`, if the network has one.
+    pub fn ws_url(&self) -> Option<&'static str> {
+        match self {
+            Network::BaseSepolia => Some("wss://base-sepolia-rpc.publicnode.com"),
+            Network::BaseMainnet => Some("wss://base-rpc.publicnode.com"),
+            Network::EthSepolia => Some("wss://ethereum-sepolia-rpc.publicnode.com"),
+            Network::Anvil => Some("ws://127.0.0.1:8545"),
+        }
+    }
+
     pub fn rpc_url(&self) -> &'static str {
+        self.rpc_urls()[0]
+    }
+
+    pub fn explorer_url(&self) -> Option<&'static str> {
         match self {
-            Network::BaseSepolia => "https://sepolia.base.org",
+            Network::BaseSepolia => Some("https://sepolia.basescan.org"),
+            Network::BaseMainnet => Some("https://basescan.org"),
+            Network::EthSepolia => Some("https://sepolia.etherscan.io"),
+            Network::Anvil => None,
+        }
+    }
 
+    pub fn address_url(&self, address: &str) -> Option<String> {
+        self.explorer_url().map(|base| format!("{}/address/{}", base, address))
+    }
+
+    pub fn tx_url(&self, hash: &str) -> Option<String> {
+        self.explorer_url().map(|base| format!("{}/tx/{}", base, hash))
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        match self {
+            // Public endpoints rate limit aggressively; back off further
+            Network::BaseSepolia | Network::BaseMainnet | Network::EthSepolia => RetryPolicy {
+                max_attempts: 4,
+                base_delay_ms: 250,
+                max_delay_ms: 8_000,
+            },
+            // A local node is either up or not
+            Network::Anvil => RetryPolicy {
+                max_attempts: 2,
+                base_delay_ms: 100,
+                max_delay_ms: 500,
+            },
         }
     }
+
+    pub fn contracts(&self) -> Contracts {
+        match self {
+            Network::BaseSepolia => Contracts {
+                erc6551_registry: Some(ERC6551_REGISTRY),
+                tba_implementation: Some("0xfb28ae9ffc69dd62718a780cb657a59c0b4e7aae"),
+                identity_nft: Some("0x66994e547cb9014191f50c7c7ee8cf5e80d3b89e"),
+                deployer: Some("0x769c18faa2e2e833a262c2ff9f6e1a9e99e52c58"),
+            },
+            Network::BaseMainnet | Network::EthSepolia => Contracts {
+                erc6551_registry: Some(ERC6551_REGISTRY),
+                ..Contracts::default()
+            },
+            // Default anvil account 0
+            Network::Anvil => Contracts {
+                deployer: Some("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"),
+                ..Contracts::default()
+            },
+        }
+    }
+}
+
+/// Why an RPC request failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RpcError {
+    /// The request never got a response (offline, DNS, CORS, ...)
+    Transport(String),
+    /// The endpoint answered with a non-2xx status
+    Http { status: u16, body: String, retry_after_ms: Option<u64> },
+    /// The node returned a JSON-RPC `error` object
+    JsonRpc { code: i64, message: String, data: Option<serde_json::Value> },
+    /// The response was not the JSON we expected
+    Decode(String),
+    /// Endpoints disagreed on a quorum read
+    NoQuorum { agreeing: usize, required: usize },
+    /// A contract call reverted; `data` is the hex revert payload
+    Reverted { data: String },
+    /// Arguments or return data did not match the function's ABI
+    Abi(String),
+    /// The endpoint serves a different chain than the network it is configured for
+    WrongChain { url: String, expected: u64, actual: u64 },
+    /// A proof did not check out against a trusted state root
+    Unverified(String),
 }
 
-pub async fn eth_call(network: Network, method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
-    let rpc_url = network.rpc_url();
-    
+impl RpcError {
+    fn message_contains(&self, needle: &str) -> bool {
+        matches!(self, RpcError::JsonRpc { message, .. } if message.to_lowercase().contains(needle))
+    }
+
+    pub fn is_nonce_too_low(&self) -> bool {
+        self.message_contains("nonce too low")
+    }
+
+    pub fn is_insufficient_funds(&self) -> bool {
+        self.message_contains("insufficient funds")
+    }
+
+    /// HTTP 429, or the -32005 "limit exceeded" code many providers use.
+    pub fn is_rate_limited(&self) -> bool {
+        match self {
+            RpcError::Http { status, .. } => *status == 429,
+            RpcError::JsonRpc { code, .. } => *code == -32005 || self.message_contains("rate limit"),
+            _ => false,
+        }
+    }
+
+    /// ABI-encoded revert data of a failed call or gas estimate, if any.
+    pub fn revert_data(&self) -> Option<&str> {
+        match self {
+            RpcError::JsonRpc { data: Some(data), .. } => data.as_str().or_else(|| data.get("data").and_then(|d| d.as_str())),
+            RpcError::Reverted { data } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// The `require` message or panic code behind `revert_data`.
+    pub fn revert_reason(&self) -> Option<String> {
+        let data = alloy_primitives::hex::decode(self.revert_data()?.trim_start_matches("0x")).ok()?;
+        crate::abi::decode_revert(&data)
+    }
+
+    /// Few words for tight spots like a balance cell.
+    pub fn label(&self) -> String {
+        match self {
+            _ if self.is_rate_limited() => "Rate limited".to_string(),
+            RpcError::Transport(_) => "Offline".to_string(),
+            RpcError::Http { status, .. } => format!("HTTP {}", status),
+            RpcError::JsonRpc { code, .. } => format!("RPC {}", code),
+            RpcError::Decode(_) => "Bad response".to_string(),
+            RpcError::NoQuorum { .. } => "No quorum".to_string(),
+            RpcError::Reverted { .. } => "Reverted".to_string(),
+            RpcError::Abi(_) => "ABI mismatch".to_string(),
+            RpcError::WrongChain { .. } => "Wrong chain".to_string(),
+            RpcError::Unverified(_) => "Unverified".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcError::Transport(e) => write!(f, "Network error: {}", e),
+            RpcError::Http { status, body, .. } => write!(f, "HTTP {}: {}", status, body),
+            RpcError::JsonRpc { code, message, .. } => write!(f, "{} (code {})", message, code),
+            RpcError::Decode(e) => write!(f, "Invalid response: {}", e),
+            RpcError::NoQuorum { agreeing, required } => write!(f, "Endpoints disagree ({} of {} agree)", agreeing, required),
+            RpcError::Reverted { data } => match self.revert_reason() {
+                Some(reason) => write!(f, "Execution reverted: {}", reason),
+                None => write!(f, "Execution reverted (data {})", data),
+            },
+            RpcError::Abi(e) => write!(f, "ABI error: {}", e),
+            RpcError::WrongChain { url, expected, actual } => write!(f, "{} serves chain {}, not {}", url, actual, expected),
+            RpcError::Unverified(e) => write!(f, "Proof check failed: {}", e),
+        }
+    }
+}
+
+fn parse_quantity<T, E: std::fmt::Display>(val: &serde_json::Value, parse: fn(&str, u32) -> Result<T, E>) -> Result<T, RpcError> {
+    let hex = val.as_str().ok_or_else(|| RpcError::Decode(format!("expected hex quantity, got {}", val)))?;
+    parse(hex.trim_start_matches("0x"), 16).map_err(|e| RpcError::Decode(e.to_string()))
+}
+
+fn decode_balance(val: serde_json::Value) -> Result<Amount, RpcError> {
+    decode_u256(val).map(Amount::wei)
+}
+
+pub fn decode_u64(val: serde_json::Value) -> Result<u64, RpcError> {
+    parse_quantity(&val, u64::from_str_radix)
+}
+
+fn decode_u128(val: serde_json::Value) -> Result<u128, RpcError> {
+    parse_quantity(&val, u128::from_str_radix)
+}
+
+pub fn decode_u256(val: serde_json::Value) -> Result<ethers_core::types::U256, RpcError> {
+    parse_quantity(&val, ethers_core::types::U256::from_str_radix)
+}
+
+/// Splits one response object into its `result` or `error`.
+pub fn into_result(mut json: serde_json::Value) -> Result<serde_json::Value, RpcError> {
+    if let Some(err) = json.get("error") {
+        return Err(RpcError::JsonRpc {
+            code: err["code"].as_i64().unwrap_or(0),
+            message: err["message"].as_str().unwrap_or("unknown error").to_string(),
+            data: err.get("data").cloned(),
+        });
+    }
+
+    match json.get_mut("result") {
+        Some(result) => Ok(result.take()),
+        None => Err(RpcError::Decode("missing result".to_string())),
+    }
+}
+
+/// Sends one JSON-RPC request and returns its `result`. Reads go through
+/// `cache`: immutable results are kept, block-tagged ones until the next head,
+/// and identical reads already in flight are shared.
+pub async fn eth_call(network: Network, method: &str, params: serde_json::Value) -> Result<serde_json::Value, RpcError> {
+    if !is_idempotent(method) {
+        return fetch(network, method, params).await;
+    }
+    let key = crate::cache::key(network, method, &params);
+    if let Some(hit) = crate::cache::lookup(network, &key) {
+        return Ok(hit);
+    }
+    let claim = match crate::cache::InFlight::start(&key) {
+        Ok(claim) => claim,
+        Err(joined) => return joined.await.unwrap_or(Err(RpcError::Transport("request was cancelled".to_string()))),
+    };
+    let result = fetch(network, method, params.clone()).await;
+    if let Ok(value) = &result {
+        crate::cache::insert(network, method, &params, key, value);
+    }
+    claim.finish(&result);
+    result
+}
+
+async fn fetch(network: Network, method: &str, params: serde_json::Value) -> Result<serde_json::Value, RpcError> {
     let body = json!({
         "jsonrpc": "2.0",
         "method": method,
         "params": params,
         // Random ID to avoid caching potentially
-        "id": (js_sys::Math::random() * 1000.0) as u32
+        "id": (rand::random::<f64>() * 1000.0) as u32
     });
 
-    let resp = Request::post(rpc_url)
-        .header("Content-Type", "application/json")
-        .body(body.to_string())
-        .map_err(|e| e.to_string())?
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+    into_result(crate::provider::post(network, &body, is_idempotent(method)).await?)
+}
+
+/// Handle to one call in a `Batch`; redeem it with `BatchResponse::get`.
+pub struct Pending<T> {
+    id: usize,
+    decode: fn(serde_json::Value) -> Result<T, RpcError>,
+}
 
-    let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
-    
-    if let Some(err) = json.get("error") {
-        return Err(err.to_string());
+/// Several JSON-RPC calls sent in a single HTTP POST.
+#[derive(Default)]
+pub struct Batch {
+    requests: Vec<serde_json::Value>,
+    /// Ids that must reach the read quorum (see `provider::set_read_quorum`)
+    critical: Vec<usize>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a call whose `result` is decoded with `decode`.
+    pub fn call<T>(&mut self, method: &str, params: serde_json::Value, decode: fn(serde_json::Value) -> Result<T, RpcError>) -> Pending<T> {
+        let id = self.requests.len();
+        self.requests.push(json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": id }));
+        Pending { id, decode }
+    }
+
+    fn critical<T>(&mut self, pending: Pending<T>) -> Pending<T> {
+        self.critical.push(pending.id);
+        pending
     }
 
-    Ok(json)
+    pub fn balance(&mut self, address: &str) -> Pending<Amount> {
+        let p = self.call("eth_getBalance", json!([address, "latest"]), decode_balance);
+        self.critical(p)
+    }
+
+    pub fn transaction_count(&mut self, address: &str) -> Pending<u64> {
+        let p = self.call("eth_getTransactionCount", json!([address, "latest"]), decode_u64);
+        self.critical(p)
+    }
+
+    pub fn gas_price(&mut self) -> Pending<u128> {
+        self.call("eth_gasPrice", json!([]), decode_u128)
+    }
+
+    pub fn estimate_gas(&mut self, tx: serde_json::Value) -> Pending<ethers_core::types::U256> {
+        self.call("eth_estimateGas", json!([tx]), decode_u256)
+    }
+
+    /// Fails as a whole only if the POST itself fails; each call still
+    /// carries its own result.
+    pub async fn send(self, network: Network) -> Result<BatchResponse, RpcError> {
+        let count = self.requests.len();
+        let idempotent = self.requests.iter().all(|r| r["method"].as_str().is_some_and(is_idempotent));
+        let body = serde_json::Value::Array(self.requests);
+        // Quorum needs as many endpoints as it counts
+        let quorum = crate::provider::read_quorum().min(network.rpc_urls().len());
+        if quorum <= 1 || self.critical.is_empty() {
+            let results = split_batch(crate::provider::post(network, &body, idempotent).await?, count)?;
+            return Ok(BatchResponse { results: results.into_iter().map(|r| r.map(into_result)).collect() });
+        }
+
+        let answers: Vec<_> = crate::provider::post_many(network, &body, quorum).await
+            .into_iter()
+            .map(|r| r.and_then(|b| split_batch(b, count)))
+            .collect();
+        // Non-critical calls take the first endpoint that answered at all
+        let first = answers.iter().find_map(|a| a.as_ref().ok()).cloned();
+        let Some(first) = first else {
+            return Err(answers.into_iter().find_map(Result::err).unwrap());
+        };
+        let mut results: Vec<_> = first.into_iter().map(|r| r.map(into_result)).collect();
+        for &id in &self.critical {
+            let votes: Vec<_> = answers.iter()
+                .filter_map(|a| a.as_ref().ok())
+                .filter_map(|r| r[id].as_ref().and_then(|resp| resp.get("result")))
+                .collect();
+            let count_of = |v: &&serde_json::Value| votes.iter().filter(|w| *w == v).count();
+            let winner = votes.iter().max_by_key(|v| count_of(v));
+            let agreeing = winner.map(count_of).unwrap_or(0);
+            results[id] = Some(match winner {
+                Some(v) if agreeing >= quorum => Ok((*v).clone()),
+                _ => Err(RpcError::NoQuorum { agreeing, required: quorum }),
+            });
+        }
+        Ok(BatchResponse { results })
+    }
 }
 
-pub async fn get_balance(address: &str, network: Network) -> String {
-    let params = json!([address, "latest"]);
-    match eth_call(network, "eth_getBalance", params).await {
-        Ok(val) => {
-            let hex = val["result"].as_str().unwrap_or("0x0");
-            let wei = u128::from_str_radix(hex.trim_start_matches("0x"), 16).unwrap_or(0);
-            format!("{:.4} ETH", wei as f64 / 1e18)
-        },
-        Err(_) => "Error".to_string()
+/// Orders a batch response by id. Responses may arrive in any order.
+fn split_batch(body: serde_json::Value, count: usize) -> Result<Vec<Option<serde_json::Value>>, RpcError> {
+    // A batch-level error (e.g. batching unsupported) comes back as one object
+    let serde_json::Value::Array(responses) = body else {
+        return Err(into_result(body).err().unwrap_or(RpcError::Decode("expected batch response".to_string())));
+    };
+
+    let mut results: Vec<Option<serde_json::Value>> = vec![None; count];
+    for resp in responses {
+        if let Some(slot) = resp["id"].as_u64().and_then(|id| results.get_mut(id as usize)) {
+            *slot = Some(resp);
+        }
     }
+    Ok(results)
 }
 
-pub async fn get_transaction_count(address: &str, network: Network) -> Result<u64, String> {
-    let params = json!([address, "latest"]);
-    let val = eth_call(network, "eth_getTransactionCount", params).await?;
-    let hex = val["result"].as_str().unwrap_or("0x0");
-    u64::from_str_radix(hex.trim_start_matches("0x"), 16).map_err(|e| e.to_string())
+pub struct BatchResponse {
+    results: Vec<Option<Result<serde_json::Value, RpcError>>>,
+}
+
+impl BatchResponse {
+    pub fn get<T>(&mut self, pending: Pending<T>) -> Result<T, RpcError> {
+        let result = self.results.get_mut(pending.id).and_then(Option::take)
+            .ok_or_else(|| RpcError::Decode(format!("no response for id {}", pending.id)))?;
+        result.and_then(pending.decode)
+    }
 }
 
-pub async fn get_gas_price(network: Network) -> Result<u128, String> {
-    let val = eth_call(network, "eth_gasPrice", json!([])).await?;
-    let hex = val["result"].as_str().unwrap_or("0x0");
-    u128::from_str_radix(hex.trim_start_matches("0x"), 16).map_err(|e| e.to_string())
+pub async fn get_balance(network: Network, address: &str) -> Result<Amount, RpcError> {
+    decode_balance(eth_call(network, "eth_getBalance", json!([address, "latest"])).await?)
 }
 
-pub async fn send_raw_transaction(hex_tx: &str, network: Network) -> Result<String, String> {
+pub async fn send_raw_transaction(hex_tx: &str, network: Network) -> Result<String, RpcError> {
     let params = json!([hex_tx]);
     let val = eth_call(network, "eth_sendRawTransaction", params).await?;
-    val["result"].as_str().map(|s| s.to_string()).ok_or("No tx hash returned".to_string())
+    val.as_str().map(|s| s.to_string()).ok_or(RpcError::Decode("No tx hash returned".to_string()))
 }
 
-pub async fn get_transaction_receipt(tx_hash: &str, network: Network) -> Result<serde_json::Value, String> {
+/// `Value::Null` if the node has never seen the transaction.
+pub async fn get_transaction_by_hash(tx_hash: &str, network: Network) -> Result<serde_json::Value, RpcError> {
+    eth_call(network, "eth_getTransactionByHash", json!([tx_hash])).await
+}
+
+/// `Value::Null` while the transaction is pending.
+pub async fn get_transaction_receipt(tx_hash: &str, network: Network) -> Result<serde_json::Value, RpcError> {
     let params = json!([tx_hash]);
-    let val = eth_call(network, "eth_getTransactionReceipt", params).await?;
-    Ok(val["result"].clone())
+    eth_call(network, "eth_getTransactionReceipt", params).await
+}
+
+/// Block a read is evaluated at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlockTag {
+    Latest,
+    Number(u64),
+}
+
+impl BlockTag {
+    fn param(&self) -> serde_json::Value {
+        match self {
+            BlockTag::Latest => json!("latest"),
+            BlockTag::Number(n) => json!(format!("0x{:x}", n)),
+        }
+    }
+}
+
+/// Runs `function(args)` on the contract at `to` as an `eth_call` and
+/// decodes the return values. A revert comes back as `RpcError::Reverted`.
+pub async fn call_contract(
+    network: Network,
+    to: &str,
+    function: &crate::abi::ContractFn,
+    args: &[ethers_core::abi::Token],
+    block: BlockTag,
+) -> Result<Vec<ethers_core::abi::Token>, RpcError> {
+    use alloy_primitives::hex;
+
+    let data = function.encode(args).map_err(RpcError::Abi)?;
+    let params = json!([{ "to": to, "data": format!("0x{}", hex::encode(data)) }, block.param()]);
+    let output = match eth_call(network, "eth_call", params).await {
+        Ok(output) => output,
+        Err(e) => return Err(match e.revert_data() {
+            Some(data) => RpcError::Reverted { data: data.to_string() },
+            None => e,
+        }),
+    };
+    let output = output.as_str().ok_or(RpcError::Decode("eth_call result is not a string".to_string()))?;
+    let bytes = hex::decode(output.trim_start_matches("0x")).map_err(|e| RpcError::Decode(e.to_string()))?;
+    function.decode(&bytes).map_err(RpcError::Abi)
+}
+
+pub async fn get_block_number(network: Network) -> Result<u64, RpcError> {
+    decode_u64(eth_call(network, "eth_blockNumber", json!([])).await?)
+}
+
+/// keccak256("Transfer(address,address,uint256)")
+pub const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+/// An `eth_subscribe` stream.
+pub enum Subscription {
+    NewHeads,
+    /// Log filter object (`address` / `topics`)
+    Logs(serde_json::Value),
+}
+
+pub enum SubscriptionEvent {
+    NewHead { number: u64 },
+    Log(serde_json::Value),
+}
+
+thread_local! {
+    static HEAD_WAITERS: RefCell<Vec<oneshot::Sender<u64>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Wakes everything waiting in `next_head` and expires block-tagged cache entries.
+pub fn notify_head(network: Network, number: u64) {
+    crate::cache::on_head(network, number);
+    for waiter in HEAD_WAITERS.with(|w| std::mem::take(&mut *w.borrow_mut())) {
+        let _ = waiter.send(number);
+    }
+}
+
+/// Resolves when the next block is announced, or after `timeout_ms` so
+/// callers fall back to polling when no subscription is live.
+pub async fn next_head(timeout_ms: u32) {
+    let (tx, rx) = oneshot::channel();
+    HEAD_WAITERS.with(|w| w.borrow_mut().push(tx));
+    futures::future::select(rx, Box::pin(crate::transport::sleep(timeout_ms))).await;
 }
 
+/// Opens a WebSocket, subscribes to `subs` and reports each notification.
+/// Only returns once the socket fails or closes. Browser only.
+pub async fn subscribe(network: Network, subs: &[Subscription], mut on_event: impl FnMut(SubscriptionEvent)) -> Result<(), RpcError> {
+    let url = network.ws_url().ok_or(RpcError::Transport(format!("No WebSocket endpoint for {}", network.name())))?;
+    let mut ws = WebSocket::open(url).map_err(|e| RpcError::Transport(e.to_string()))?;
+
+    for (id, sub) in subs.iter().enumerate() {
+        let params = match sub {
+            Subscription::NewHeads => json!(["newHeads"]),
+            Subscription::Logs(filter) => json!(["logs", filter]),
+        };
+        let req = json!({ "jsonrpc": "2.0", "id": id, "method": "eth_subscribe", "params": params });
+        ws.send(Message::Text(req.to_string())).await.map_err(|e| RpcError::Transport(e.to_string()))?;
+    }
+
+    // Subscription id (assigned by the node) -> index into `subs`
+    let mut active: HashMap<String, usize> = HashMap::new();
+    while let Some(msg) = ws.next().await {
+        let text = match msg {
+            Ok(Message::Text(t)) => t,
+            Ok(Message::Bytes(b)) => String::from_utf8_lossy(&b).into_owned(),
+            Err(e) => return Err(RpcError::Transport(e.to_string())),
+        };
+        let json: serde_json::Value = serde_json::from_str(&text).map_err(|e| RpcError::Decode(e.to_string()))?;
+
+        if let Some(index) = json["id"].as_u64() {
+            // Reply to one of our eth_subscribe calls
+            let sub_id = into_result(json)?;
+            let sub_id = sub_id.as_str().ok_or(RpcError::Decode("subscription id is not a string".to_string()))?;
+            active.insert(sub_id.to_string(), index as usize);
+        } else if json["method"] == "eth_subscription" {
+            let params = &json["params"];
+            let Some(&index) = params["subscription"].as_str().and_then(|id| active.get(id)) else { continue; };
+            match subs[index] {
+                Subscription::NewHeads => {
+                    let number = decode_u64(params["result"]["number"].clone())?;
+                    notify_head(network, number);
+                    on_event(SubscriptionEvent::NewHead { number });
+                },
+                Subscription::Logs(_) => on_event(SubscriptionEvent::Log(params["result"].clone())),
+            }
+        }
+    }
+    Err(RpcError::Transport("WebSocket closed".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::ContractFn;
+    use crate::transport::{set_transport, AnyTransport, MockTransport};
+    use ethers_core::abi::Token;
+    use ethers_core::types::{Address, U256};
+    use futures::executor::block_on;
+
+    const ADDRESS: &str = "0x00000000000000000000000000000000000000a1";
+
+    fn mock() -> MockTransport {
+        let mock = MockTransport::new();
+        set_transport(AnyTransport::Mock(mock.clone()));
+        mock
+    }
+
+    fn account_fn() -> ContractFn {
+        ContractFn::parse(
+            "function account(address implementation, bytes32 salt, uint256 chainId, address tokenContract, uint256 tokenId) view returns (address)"
+        ).unwrap()
+    }
 
+    #[test]
+    fn batch_matches_replies_by_id() {
+        let mock = mock();
+        mock.respond("eth_getBalance", json!("0x64"))
+            .respond("eth_gasPrice", json!("0x3b9aca00"))
+            .respond("eth_getTransactionCount", json!("0x7"))
+            .fail("eth_estimateGas", -32000, "gas required exceeds allowance");
 
-pub async fn estimate_gas(tx: serde_json::Value, network: Network) -> Result<ethers_core::types::U256, String> {
-    let val = eth_call(network, "eth_estimateGas", json!([tx])).await?;
-    let hex = val["result"].as_str().unwrap_or("0x0");
-    ethers_core::types::U256::from_str_radix(hex.trim_start_matches("0x"), 16).map_err(|e| e.to_string())
+        let mut batch = Batch::new();
+        let balance = batch.balance(ADDRESS);
+        let gas_price = batch.gas_price();
+        let nonce = batch.transaction_count(ADDRESS);
+        let gas = batch.estimate_gas(json!({ "to": ADDRESS }));
+        // The mock answers batches in reverse order
+        let mut response = block_on(batch.send(Network::BaseSepolia)).unwrap();
+
+        assert_eq!(response.get(nonce), Ok(7));
+        assert_eq!(response.get(gas_price), Ok(1_000_000_000));
+        assert_eq!(response.get(balance), Ok(Amount::wei(100)));
+        assert!(matches!(response.get(gas), Err(RpcError::JsonRpc { code: -32000, .. })));
+        // One POST to one endpoint
+        let urls = Network::BaseSepolia.rpc_urls();
+        assert_eq!(mock.requests_to(urls[0]).len(), 4);
+        assert!(mock.requests_to(urls[1]).is_empty());
+    }
+
+    #[test]
+    fn split_batch_handles_batch_errors_and_gaps() {
+        // A single error object instead of an array fails the whole batch
+        let body = json!({ "jsonrpc": "2.0", "id": null, "error": { "code": -32600, "message": "batching disabled" } });
+        assert!(matches!(split_batch(body, 2), Err(RpcError::JsonRpc { code: -32600, .. })));
+        // Missing and unknown ids leave their slot empty
+        let results = split_batch(json!([{ "id": 1, "result": "0x1" }, { "id": 9, "result": "0x2" }]), 2).unwrap();
+        assert_eq!(results, [None, Some(json!({ "id": 1, "result": "0x1" }))]);
+    }
+
+    #[test]
+    fn critical_reads_need_a_quorum() {
+        let mock = mock();
+        crate::provider::set_read_quorum(2);
+        let urls = Network::BaseSepolia.rpc_urls();
+        mock.respond_at(urls[0], "eth_getTransactionCount", json!("0x7"))
+            .respond_at(urls[1], "eth_getTransactionCount", json!("0x8"))
+            .respond("eth_gasPrice", json!("0x1"));
+
+        let mut batch = Batch::new();
+        let nonce = batch.transaction_count(ADDRESS);
+        let gas_price = batch.gas_price();
+        let mut response = block_on(batch.send(Network::BaseSepolia)).unwrap();
+
+        assert_eq!(response.get(nonce), Err(RpcError::NoQuorum { agreeing: 1, required: 2 }));
+        // Non-critical calls are not voted on
+        assert_eq!(response.get(gas_price), Ok(1));
+        assert_eq!(mock.requests_to(urls[0]).len(), 2);
+        assert_eq!(mock.requests_to(urls[1]).len(), 2);
+    }
+
+    #[test]
+    fn agreeing_endpoints_reach_the_quorum() {
+        let mock = mock();
+        crate::provider::set_read_quorum(2);
+        mock.respond("eth_getTransactionCount", json!("0x7"));
+
+        let mut batch = Batch::new();
+        let nonce = batch.transaction_count(ADDRESS);
+        assert_eq!(block_on(batch.send(Network::BaseSepolia)).unwrap().get(nonce), Ok(7));
+
+        // An unreachable endpoint leaves a lone vote
+        mock.unreachable(Network::BaseSepolia.rpc_urls()[1]);
+        let mut batch = Batch::new();
+        let nonce = batch.transaction_count(ADDRESS);
+        let mut response = block_on(batch.send(Network::BaseSepolia)).unwrap();
+        assert_eq!(response.get(nonce), Err(RpcError::NoQuorum { agreeing: 1, required: 2 }));
+    }
+
+    #[test]
+    fn call_contract_encodes_and_decodes() {
+        let mock = mock();
+        mock.respond("eth_call", json!(format!("0x{:0>64}", "ab".repeat(20))));
+        let args = [
+            Token::Address(Address::zero()),
+            Token::FixedBytes(vec![0; 32]),
+            Token::Uint(U256::from(84532)),
+            Token::Address(Address::zero()),
+            Token::Uint(U256::one()),
+        ];
+
+        let out = block_on(call_contract(Network::BaseSepolia, ERC6551_REGISTRY, &account_fn(), &args, BlockTag::Number(5))).unwrap();
+        assert_eq!(out, [Token::Address(Address::repeat_byte(0xab))]);
+
+        let sent = &mock.requests()[0];
+        assert_eq!(sent["method"], "eth_call");
+        assert_eq!(sent["params"][0]["to"], ERC6551_REGISTRY);
+        assert!(sent["params"][0]["data"].as_str().unwrap().starts_with("0x246a0021"));
+        assert_eq!(sent["params"][1], "0x5");
+    }
+
+    #[test]
+    fn call_contract_surfaces_reverts() {
+        let mock = mock();
+        let reason = ethers_core::abi::encode(&[Token::String("not the owner".to_string())]);
+        let data = format!("0x08c379a0{}", alloy_primitives::hex::encode(reason));
+        mock.revert("eth_call", &data);
+        let args = [
+            Token::Address(Address::zero()),
+            Token::FixedBytes(vec![0; 32]),
+            Token::Uint(U256::one()),
+            Token::Address(Address::zero()),
+            Token::Uint(U256::one()),
+        ];
+
+        let err = block_on(call_contract(Network::BaseSepolia, ERC6551_REGISTRY, &account_fn(), &args, BlockTag::Latest)).unwrap_err();
+        assert_eq!(err, RpcError::Reverted { data });
+        assert_eq!(err.revert_reason().as_deref(), Some("not the owner"));
+
+        // Wrong arity never reaches the node
+        let err = block_on(call_contract(Network::BaseSepolia, ERC6551_REGISTRY, &account_fn(), &args[..2], BlockTag::Latest)).unwrap_err();
+        assert!(matches!(err, RpcError::Abi(_)));
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[test]
+    fn call_contract_rejects_short_return_data() {
+        let mock = mock();
+        mock.respond("eth_call", json!("0x"));
+        let args = [
+            Token::Address(Address::zero()),
+            Token::FixedBytes(vec![0; 32]),
+            Token::Uint(U256::one()),
+            Token::Address(Address::zero()),
+            Token::Uint(U256::one()),
+        ];
+        let err = block_on(call_contract(Network::BaseSepolia, ERC6551_REGISTRY, &account_fn(), &args, BlockTag::Latest)).unwrap_err();
+        assert!(matches!(err, RpcError::Abi(_)), "{:?}", err);
+    }
 }
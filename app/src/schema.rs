@@ -0,0 +1,120 @@
+use serde_json::{json, Value};
+
+/// On-disk version of `Keystore`, written as its `schema` field.
+/// Bump it together with a new entry in `MIGRATIONS`.
+pub const KEYSTORE_SCHEMA: u32 = 1;
+
+/// `MIGRATIONS[n]` upgrades a keystore from schema `n` to `n + 1`.
+/// A legacy plaintext key found on the way is moved into the second argument.
+type Migration = fn(&mut serde_json::Map<String, Value>, &mut Option<String>) -> Result<(), String>;
+const MIGRATIONS: &[Migration] = &[v0_to_v1];
+
+/// Brings a stored or imported keystore up to `KEYSTORE_SCHEMA`.
+/// Returns the plaintext private key of pre-encryption keystores, if any.
+pub fn migrate_keystore(value: &mut Value) -> Result<Option<String>, String> {
+    let obj = value.as_object_mut().ok_or("Keystore is not a JSON object")?;
+    let mut version = match obj.get("schema") {
+        // Written before versioning
+        None => 0,
+        Some(v) => v.as_u64().ok_or("Keystore schema is not a number")? as u32,
+    };
+    if version > KEYSTORE_SCHEMA {
+        return Err(format!(
+            "Keystore schema v{} is newer than this app supports (v{}). Update the wallet to open it.",
+            version, KEYSTORE_SCHEMA
+        ));
+    }
+
+    let mut legacy_key = None;
+    while version < KEYSTORE_SCHEMA {
+        MIGRATIONS[version as usize](obj, &mut legacy_key)
+            .map_err(|e| format!("Keystore migration v{} -> v{} failed: {}", version, version + 1, e))?;
+        version += 1;
+        obj.insert("schema".to_string(), json!(version));
+    }
+    Ok(legacy_key)
+}
+
+// v0: unversioned. Either the original plaintext `{private_key, address,
+// smart_account}` or an encrypted keystore whose HD/source fields may be missing.
+fn v0_to_v1(obj: &mut serde_json::Map<String, Value>, legacy_key: &mut Option<String>) -> Result<(), String> {
+    if let Some(pk) = obj.remove("private_key") {
+        let pk = pk.as_str().ok_or("private_key is not a string")?;
+        *legacy_key = Some(pk.to_string());
+        obj.insert("crypto".to_string(), Value::Null);
+    } else if obj.get("crypto").is_none_or(Value::is_null) && obj.get("source").is_none() {
+        return Err("neither an encrypted nor a plaintext key".to_string());
+    }
+    if !obj.get("address").is_some_and(Value::is_string) {
+        return Err("missing address".to_string());
+    }
+    obj.entry("smart_account").or_insert(Value::Null);
+    obj.entry("source").or_insert(json!({ "type": "random" }));
+    obj.entry("accounts").or_insert(json!([]));
+    obj.entry("active_account").or_insert(json!(0));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v0_plaintext_key_is_handed_back() {
+        let mut keystore = json!({ "private_key": "0xabc", "address": "0x01", "smart_account": "0x02" });
+        let legacy_key = migrate_keystore(&mut keystore).unwrap();
+        assert_eq!(legacy_key.as_deref(), Some("0xabc"));
+        assert_eq!(keystore, json!({
+            "schema": 1,
+            "address": "0x01",
+            "smart_account": "0x02",
+            "crypto": null,
+            "source": { "type": "random" },
+            "accounts": [],
+            "active_account": 0
+        }));
+    }
+
+    #[test]
+    fn v0_encrypted_keystore_gains_defaults() {
+        let mut keystore = json!({ "address": "0x01", "crypto": { "cipher": "aes-128-ctr" } });
+        assert_eq!(migrate_keystore(&mut keystore).unwrap(), None);
+        assert_eq!(keystore["schema"], 1);
+        assert_eq!(keystore["crypto"]["cipher"], "aes-128-ctr");
+        assert_eq!(keystore["smart_account"], Value::Null);
+        assert_eq!(keystore["source"], json!({ "type": "random" }));
+    }
+
+    #[test]
+    fn v0_without_any_key_is_rejected() {
+        let mut keystore = json!({ "address": "0x01" });
+        let err = migrate_keystore(&mut keystore).unwrap_err();
+        assert_eq!(err, "Keystore migration v0 -> v1 failed: neither an encrypted nor a plaintext key");
+
+        let mut keystore = json!({ "private_key": "0xabc" });
+        assert!(migrate_keystore(&mut keystore).unwrap_err().ends_with("missing address"));
+    }
+
+    #[test]
+    fn current_schema_is_left_alone() {
+        let mut keystore = json!({ "schema": KEYSTORE_SCHEMA, "address": "0x01" });
+        let before = keystore.clone();
+        assert_eq!(migrate_keystore(&mut keystore).unwrap(), None);
+        assert_eq!(keystore, before);
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() {
+        let mut keystore = json!({ "schema": KEYSTORE_SCHEMA + 1, "address": "0x01" });
+        let before = keystore.clone();
+        let err = migrate_keystore(&mut keystore).unwrap_err();
+        assert!(err.starts_with("Keystore schema v2 is newer than this app supports (v1)"), "{}", err);
+        assert_eq!(keystore, before);
+    }
+
+    #[test]
+    fn malformed_schema_is_rejected() {
+        assert!(migrate_keystore(&mut json!([])).is_err());
+        assert_eq!(migrate_keystore(&mut json!({ "schema": "1" })).unwrap_err(), "Keystore schema is not a number");
+    }
+}
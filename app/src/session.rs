@@ -0,0 +1,49 @@
+use ethers_signers::LocalWallet;
+use zeroize::Zeroizing;
+
+/// Decrypted key material of an unlocked wallet.
+/// Every copy zeroizes its buffers when dropped, so locking (dropping the
+/// session signal's value) wipes the key once in-flight actions finish.
+#[derive(Clone)]
+pub struct Session {
+    key: Zeroizing<Vec<u8>>,
+    /// BIP-39 phrase, kept to derive and switch HD accounts
+    phrase: Option<Zeroizing<String>>,
+}
+
+impl Session {
+    pub fn new(wallet: &LocalWallet, phrase: Option<String>) -> Self {
+        Self {
+            key: Zeroizing::new(wallet.signer().to_bytes().to_vec()),
+            phrase: phrase.map(Zeroizing::new),
+        }
+    }
+
+    /// Wraps a plaintext hex key (legacy keystores).
+    pub fn from_hex(pk: &str) -> Result<Self, String> {
+        let key = Zeroizing::new(alloy_primitives::hex::decode(pk.trim_start_matches("0x")).map_err(|e| e.to_string())?);
+        LocalWallet::from_bytes(&key).map_err(|e| e.to_string())?;
+        Ok(Self { key, phrase: None })
+    }
+
+    /// A fresh signer for one action; its signing key also zeroizes on drop.
+    pub fn wallet(&self) -> LocalWallet {
+        LocalWallet::from_bytes(&self.key).expect("session key was validated")
+    }
+
+    pub fn key_bytes(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn phrase(&self) -> Option<&str> {
+        self.phrase.as_ref().map(|p| p.as_str())
+    }
+
+    /// Same phrase, different active signer.
+    pub fn with_wallet(&self, wallet: &LocalWallet) -> Self {
+        Self {
+            key: Zeroizing::new(wallet.signer().to_bytes().to_vec()),
+            phrase: self.phrase.clone(),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use crate::fees::Speed;
+use crate::rpc::Network;
+use crate::storage::StorageBackend;
+
+pub const SETTINGS_KEY: &str = "diamond_wallet_settings";
+
+/// User preferences, persisted next to the vault.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// Idle minutes before the session key is wiped (0 = never)
+    pub auto_lock_minutes: u32,
+    /// Network every RPC call and signature targets
+    pub network: Network,
+    /// Endpoints that must agree on nonce and balance reads (1 = off)
+    pub rpc_quorum: u8,
+    /// Keep immutable RPC responses (receipts, code) across reloads
+    pub persist_rpc_cache: bool,
+    /// Fee level every transaction is priced at
+    pub fee_speed: Speed,
+    /// Prove balances with eth_getProof against a majority-agreed header
+    pub verify_balances: bool,
+    /// Networks signed with legacy (type 0) transactions instead of EIP-1559,
+    /// e.g. an anvil started with a pre-London `--hardfork`
+    pub legacy_networks: Vec<Network>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { auto_lock_minutes: 5, network: Network::default(), rpc_quorum: 1, persist_rpc_cache: true, fee_speed: Speed::default(), verify_balances: false, legacy_networks: Vec::new() }
+    }
+}
+
+impl Settings {
+    pub async fn load(store: &impl StorageBackend) -> Settings {
+        crate::storage::get::<Settings>(store, SETTINGS_KEY).await.ok().flatten().unwrap_or_default()
+    }
+
+    pub async fn save(&self, store: &impl StorageBackend) -> Result<(), String> {
+        crate::storage::set(store, SETTINGS_KEY, self).await
+    }
+}
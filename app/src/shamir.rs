@@ -0,0 +1,293 @@
+use alloy_primitives::hex;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroizing;
+
+/// Format tag written into every share so foreign JSON is rejected early.
+const SHARE_FORMAT: &str = "diamond-shamir-1";
+
+/// What the shared secret is, so recovery can rebuild the right keystore.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretKind {
+    /// 32-byte private key
+    PrivateKey,
+    /// UTF-8 BIP-39 phrase
+    Mnemonic,
+}
+
+/// One share of a split backup. Serialized as a single JSON line, so the same
+/// text works as a file or pasted into the recovery form.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Share {
+    pub format: String,
+    /// Random tag shared by all shares of one split
+    pub group: String,
+    pub threshold: u8,
+    pub total: u8,
+    /// Evaluation point x (1..=total)
+    pub index: u8,
+    pub kind: SecretKind,
+    /// Address the recovered secret must produce
+    pub address: String,
+    #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+    pub data: Vec<u8>,
+}
+
+impl Share {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let share: Share = serde_json::from_str(text.trim()).map_err(|e| format!("Not a share: {}", e))?;
+        if share.format != SHARE_FORMAT {
+            return Err(format!("Unsupported share format: {}", share.format));
+        }
+        if share.index == 0 || share.threshold == 0 || share.threshold > share.total {
+            return Err("Share header is corrupt".to_string());
+        }
+        Ok(share)
+    }
+
+    pub fn to_text(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+fn to_hex<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&hex::encode(bytes))
+}
+
+fn from_hex<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+    let s = String::deserialize(d)?;
+    hex::decode(s.trim_start_matches("0x")).map_err(serde::de::Error::custom)
+}
+
+// GF(2^8) with the AES polynomial x^8 + x^4 + x^3 + x + 1.
+// Branch-free so timing does not depend on secret bytes.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut p = 0u8;
+    for _ in 0..8 {
+        p ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    p
+}
+
+// a^254 = a^-1 for a != 0
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exp = 254u8;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Splits `secret` into `total` shares, any `threshold` of which recover it.
+pub fn split(secret: &[u8], kind: SecretKind, address: &str, threshold: u8, total: u8) -> Result<Vec<Share>, String> {
+    if threshold < 2 || threshold > total {
+        return Err(format!("Need 2 <= threshold <= shares, got {} of {}", threshold, total));
+    }
+    if secret.is_empty() {
+        return Err("Nothing to split".to_string());
+    }
+
+    let mut group = [0u8; 8];
+    OsRng.fill_bytes(&mut group);
+    let group = hex::encode(group);
+
+    let mut data = vec![Vec::with_capacity(secret.len()); total as usize];
+    // Random polynomial per byte: f(0) = secret byte, degree threshold - 1
+    let mut coeffs = Zeroizing::new(vec![0u8; threshold as usize]);
+    for &byte in secret {
+        coeffs[0] = byte;
+        OsRng.fill_bytes(&mut coeffs[1..]);
+        for (i, out) in data.iter_mut().enumerate() {
+            let x = i as u8 + 1;
+            // Horner's rule
+            let y = coeffs.iter().rev().fold(0u8, |acc, &c| gf_mul(acc, x) ^ c);
+            out.push(y);
+        }
+    }
+
+    Ok(data.into_iter().enumerate().map(|(i, data)| Share {
+        format: SHARE_FORMAT.to_string(),
+        group: group.clone(),
+        threshold,
+        total,
+        index: i as u8 + 1,
+        kind,
+        address: address.to_string(),
+        data,
+    }).collect())
+}
+
+/// Rebuilds the secret from at least `threshold` shares of the same split.
+pub fn combine(shares: &[Share]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let first = shares.first().ok_or("No shares")?;
+    for s in shares {
+        if s.group != first.group || s.kind != first.kind || s.address != first.address {
+            return Err("Shares come from different backups".to_string());
+        }
+        if s.threshold != first.threshold || s.data.len() != first.data.len() {
+            return Err("Shares are inconsistent".to_string());
+        }
+    }
+
+    // x = 0 is the secret itself and a repeated x breaks interpolation
+    for (i, s) in shares.iter().enumerate() {
+        if s.index == 0 {
+            return Err("Share header is corrupt".to_string());
+        }
+        if shares[..i].iter().any(|u| u.index == s.index) {
+            return Err(format!("Share {} was given twice", s.index));
+        }
+    }
+    if shares.len() < first.threshold as usize {
+        return Err(format!("Need {} shares, have {}", first.threshold, shares.len()));
+    }
+    let used = &shares[..first.threshold as usize];
+
+    // Lagrange interpolation at x = 0 (subtraction is XOR in GF(2^8))
+    let mut secret = Zeroizing::new(vec![0u8; first.data.len()]);
+    for (i, si) in used.iter().enumerate() {
+        let mut basis = 1u8;
+        for (j, sj) in used.iter().enumerate() {
+            if i != j {
+                basis = gf_mul(basis, gf_mul(sj.index, gf_inv(sj.index ^ si.index)));
+            }
+        }
+        for (out, &y) in secret.iter_mut().zip(&si.data) {
+            *out ^= gf_mul(y, basis);
+        }
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+    /// Every `k`-element subset of `0..n`, in lexicographic order.
+    fn subsets(n: usize, k: usize) -> Vec<Vec<usize>> {
+        if k == 0 {
+            return vec![Vec::new()];
+        }
+        (k - 1..n)
+            .flat_map(|last| subsets(last, k - 1).into_iter().map(move |mut s| { s.push(last); s }))
+            .collect()
+    }
+
+    fn pick(shares: &[Share], indices: &[usize]) -> Vec<Share> {
+        indices.iter().map(|&i| shares[i].clone()).collect()
+    }
+
+    #[test]
+    fn gf_inv_inverts_every_nonzero_element() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1, "a = {}", a);
+        }
+    }
+
+    #[test]
+    fn gf_mul_matches_known_products() {
+        // FIPS-197 section 4.2
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x57, 0x13), 0xfe);
+        for a in 0..=255u8 {
+            assert_eq!(gf_mul(a, 0), 0);
+            assert_eq!(gf_mul(a, 1), a);
+        }
+    }
+
+    #[test]
+    fn every_threshold_subset_recovers_the_secret() {
+        for (k, n) in [(2, 2), (2, 3), (3, 5), (4, 6)] {
+            let shares = split(SECRET, SecretKind::Mnemonic, "0x01", k, n).unwrap();
+            for subset in subsets(n as usize, k as usize) {
+                let secret = combine(&pick(&shares, &subset)).unwrap();
+                assert_eq!(secret.as_slice(), SECRET, "{} of {}: {:?}", k, n, subset);
+                // Order does not matter
+                let reversed: Vec<usize> = subset.iter().rev().copied().collect();
+                assert_eq!(combine(&pick(&shares, &reversed)).unwrap().as_slice(), SECRET);
+            }
+        }
+    }
+
+    #[test]
+    fn more_than_threshold_shares_recover_the_secret() {
+        let shares = split(SECRET, SecretKind::Mnemonic, "0x01", 3, 5).unwrap();
+        assert_eq!(combine(&shares).unwrap().as_slice(), SECRET);
+    }
+
+    #[test]
+    fn fewer_than_threshold_shares_do_not() {
+        let shares = split(SECRET, SecretKind::Mnemonic, "0x01", 3, 5).unwrap();
+        for subset in subsets(5, 2) {
+            assert_eq!(combine(&pick(&shares, &subset)).unwrap_err(), "Need 3 shares, have 2");
+        }
+        // Interpolating k - 1 shares as if they were enough gives the wrong secret
+        let mut short = pick(&shares, &[0, 1]);
+        for s in &mut short {
+            s.threshold = 2;
+        }
+        assert_ne!(combine(&short).unwrap().as_slice(), SECRET);
+    }
+
+    #[test]
+    fn shares_round_trip_through_text() {
+        let shares = split(&[7u8; 32], SecretKind::PrivateKey, "0x01", 2, 3).unwrap();
+        let parsed: Vec<Share> = shares.iter().map(|s| Share::parse(&s.to_text()).unwrap()).collect();
+        assert_eq!(combine(&parsed[1..]).unwrap().as_slice(), &[7u8; 32]);
+    }
+
+    #[test]
+    fn mixed_backups_are_rejected() {
+        let a = split(SECRET, SecretKind::Mnemonic, "0x01", 2, 3).unwrap();
+        let b = split(SECRET, SecretKind::Mnemonic, "0x01", 2, 3).unwrap();
+        assert_eq!(combine(&[a[0].clone(), b[1].clone()]).unwrap_err(), "Shares come from different backups");
+
+        let mut other_kind = a[1].clone();
+        other_kind.kind = SecretKind::PrivateKey;
+        assert_eq!(combine(&[a[0].clone(), other_kind]).unwrap_err(), "Shares come from different backups");
+
+        let mut other_address = a[1].clone();
+        other_address.address = "0x02".to_string();
+        assert_eq!(combine(&[a[0].clone(), other_address]).unwrap_err(), "Shares come from different backups");
+
+        let mut truncated = a[1].clone();
+        truncated.data.pop();
+        assert_eq!(combine(&[a[0].clone(), truncated]).unwrap_err(), "Shares are inconsistent");
+    }
+
+    #[test]
+    fn duplicate_x_values_are_rejected() {
+        let shares = split(SECRET, SecretKind::Mnemonic, "0x01", 2, 3).unwrap();
+        assert_eq!(combine(&pick(&shares, &[1, 1])).unwrap_err(), "Share 2 was given twice");
+        // Even with enough distinct shares besides
+        assert_eq!(combine(&pick(&shares, &[0, 2, 0])).unwrap_err(), "Share 1 was given twice");
+
+        let mut relabelled = shares[2].clone();
+        relabelled.index = 2;
+        assert_eq!(combine(&[shares[1].clone(), relabelled]).unwrap_err(), "Share 2 was given twice");
+
+        let mut zero = shares[0].clone();
+        zero.index = 0;
+        assert_eq!(combine(&[zero, shares[1].clone()]).unwrap_err(), "Share header is corrupt");
+    }
+
+    #[test]
+    fn split_rejects_bad_parameters() {
+        assert!(split(SECRET, SecretKind::Mnemonic, "0x01", 1, 3).is_err());
+        assert!(split(SECRET, SecretKind::Mnemonic, "0x01", 4, 3).is_err());
+        assert!(split(&[], SecretKind::Mnemonic, "0x01", 2, 3).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+/// Key-value persistence for wallet state (vault, settings, history).
+/// Values are JSON strings; use `get` / `set` for typed access.
+// Not Send: every backend lives on the single browser thread
+#[allow(async_fn_in_trait)]
+pub trait StorageBackend {
+    async fn get_raw(&self, key: &str) -> Result<Option<String>, String>;
+    async fn set_raw(&self, key: &str, value: &str) -> Result<(), String>;
+    async fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// Runtime-selected backend, since async trait methods are not object safe.
+#[derive(Clone)]
+pub enum Backend {
+    Local(LocalStorageBackend),
+    IndexedDb(IndexedDbBackend),
+    Memory(MemoryBackend),
+}
+
+impl Backend {
+    /// Prefers LocalStorage, then IndexedDB (e.g. when LocalStorage is blocked),
+    /// then memory (native builds and tests).
+    pub fn detect(db_name: &str) -> Self {
+        // web-sys calls panic outside the browser
+        if cfg!(not(target_arch = "wasm32")) {
+            return Backend::Memory(MemoryBackend::default());
+        }
+        let window = web_sys::window();
+        if window.as_ref().and_then(|w| w.local_storage().ok().flatten()).is_some() {
+            Backend::Local(LocalStorageBackend)
+        } else if window.as_ref().and_then(|w| w.indexed_db().ok().flatten()).is_some() {
+            Backend::IndexedDb(IndexedDbBackend::new(db_name, "kv"))
+        } else {
+            Backend::Memory(MemoryBackend::default())
+        }
+    }
+
+    /// Prefers IndexedDB, for data that grows without bound (e.g. history)
+    /// and would crowd the few megabytes of LocalStorage.
+    pub fn detect_large(db_name: &str) -> Self {
+        if cfg!(not(target_arch = "wasm32")) {
+            return Backend::Memory(MemoryBackend::default());
+        }
+        match web_sys::window().and_then(|w| w.indexed_db().ok().flatten()) {
+            Some(_) => Backend::IndexedDb(IndexedDbBackend::new(db_name, "kv")),
+            None => Backend::detect(db_name),
+        }
+    }
+}
+
+impl StorageBackend for Backend {
+    async fn get_raw(&self, key: &str) -> Result<Option<String>, String> {
+        match self {
+            Backend::Local(b) => b.get_raw(key).await,
+            Backend::IndexedDb(b) => b.get_raw(key).await,
+            Backend::Memory(b) => b.get_raw(key).await,
+        }
+    }
+
+    async fn set_raw(&self, key: &str, value: &str) -> Result<(), String> {
+        match self {
+            Backend::Local(b) => b.set_raw(key, value).await,
+            Backend::IndexedDb(b) => b.set_raw(key, value).await,
+            Backend::Memory(b) => b.set_raw(key, value).await,
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        match self {
+            Backend::Local(b) => b.delete(key).await,
+            Backend::IndexedDb(b) => b.delete(key).await,
+            Backend::Memory(b) => b.delete(key).await,
+        }
+    }
+}
+
+pub async fn get<T: DeserializeOwned>(store: &impl StorageBackend, key: &str) -> Result<Option<T>, String> {
+    match store.get_raw(key).await? {
+        Some(raw) => serde_json::from_str(&raw).map(Some).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+pub async fn set<T: Serialize>(store: &impl StorageBackend, key: &str, value: &T) -> Result<(), String> {
+    let raw = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    store.set_raw(key, &raw).await
+}
+
+/// Browser LocalStorage. Small, synchronous and shared across tabs.
+#[derive(Clone, Copy, Default)]
+pub struct LocalStorageBackend;
+
+impl StorageBackend for LocalStorageBackend {
+    async fn get_raw(&self, key: &str) -> Result<Option<String>, String> {
+        let storage = LocalStorage::raw();
+        storage.get_item(key).map_err(|_| "LocalStorage read failed".to_string())
+    }
+
+    async fn set_raw(&self, key: &str, value: &str) -> Result<(), String> {
+        let storage = LocalStorage::raw();
+        storage.set_item(key, value).map_err(|_| "LocalStorage write failed (quota?)".to_string())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        LocalStorage::delete(key);
+        Ok(())
+    }
+}
+
+/// IndexedDB object store, for data too large for LocalStorage.
+#[derive(Clone)]
+pub struct IndexedDbBackend {
+    db_name: String,
+    store_name: String,
+}
+
+impl IndexedDbBackend {
+    pub fn new(db_name: &str, store_name: &str) -> Self {
+        Self { db_name: db_name.to_string(), store_name: store_name.to_string() }
+    }
+
+    async fn open(&self) -> Result<web_sys::IdbDatabase, String> {
+        let factory = web_sys::window()
+            .and_then(|w| w.indexed_db().ok().flatten())
+            .ok_or("IndexedDB unavailable")?;
+        let request = factory.open_with_u32(&self.db_name, 1).map_err(js_err)?;
+
+        // First open (or version bump) creates the object store
+        let store_name = self.store_name.clone();
+        let req_c = request.clone();
+        let on_upgrade = Closure::once_into_js(move |_e: web_sys::Event| {
+            if let Ok(db) = req_c.result() {
+                let db: web_sys::IdbDatabase = db.unchecked_into();
+                if !db.object_store_names().contains(&store_name) {
+                    let _ = db.create_object_store(&store_name);
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+        let db = request_future(&request).await?;
+        Ok(db.unchecked_into())
+    }
+
+    async fn object_store(&self, mode: web_sys::IdbTransactionMode) -> Result<web_sys::IdbObjectStore, String> {
+        let db = self.open().await?;
+        let tx = db.transaction_with_str_and_mode(&self.store_name, mode).map_err(js_err)?;
+        tx.object_store(&self.store_name).map_err(js_err)
+    }
+}
+
+impl StorageBackend for IndexedDbBackend {
+    async fn get_raw(&self, key: &str) -> Result<Option<String>, String> {
+        let store = self.object_store(web_sys::IdbTransactionMode::Readonly).await?;
+        let request = store.get(&JsValue::from_str(key)).map_err(js_err)?;
+        Ok(request_future(&request).await?.as_string())
+    }
+
+    async fn set_raw(&self, key: &str, value: &str) -> Result<(), String> {
+        let store = self.object_store(web_sys::IdbTransactionMode::Readwrite).await?;
+        let request = store.put_with_key(&JsValue::from_str(value), &JsValue::from_str(key)).map_err(js_err)?;
+        request_future(&request).await.map(|_| ())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        let store = self.object_store(web_sys::IdbTransactionMode::Readwrite).await?;
+        let request = store.delete(&JsValue::from_str(key)).map_err(js_err)?;
+        request_future(&request).await.map(|_| ())
+    }
+}
+
+/// Resolves with the request's result once IndexedDB fires success or error.
+async fn request_future(request: &web_sys::IdbRequest) -> Result<JsValue, String> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let req_c = request.clone();
+        let on_success = Closure::once_into_js(move |_e: web_sys::Event| {
+            let _ = resolve.call1(&JsValue::NULL, &req_c.result().unwrap_or(JsValue::UNDEFINED));
+        });
+        let on_error = Closure::once_into_js(move |_e: web_sys::Event| {
+            let _ = reject.call1(&JsValue::NULL, &JsValue::from_str("IndexedDB request failed"));
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await.map_err(js_err)
+}
+
+fn js_err(e: JsValue) -> String {
+    e.as_string().unwrap_or_else(|| format!("{:?}", e))
+}
+
+/// Process-local storage. Used natively, where no browser storage exists.
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    data: Rc<RefCell<HashMap<String, String>>>,
+}
+
+impl StorageBackend for MemoryBackend {
+    async fn get_raw(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self.data.borrow().get(key).cloned())
+    }
+
+    async fn set_raw(&self, key: &str, value: &str) -> Result<(), String> {
+        self.data.borrow_mut().insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.data.borrow_mut().remove(key);
+        Ok(())
+    }
+}
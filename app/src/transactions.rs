@@ -1,8 +1,12 @@
+use std::cell::RefCell;
+
 use leptos::*;
-use ethers_core::types::{TransactionRequest, U256};
+use ethers_core::types::transaction::eip2718::TypedTransaction;
+use ethers_core::types::{Eip1559TransactionRequest, TransactionRequest, U256};
 use ethers_signers::{LocalWallet, Signer};
 use alloy_primitives::hex;
-use crate::rpc::Network;
+use crate::history::{TxKind, TxRecord};
+use crate::rpc::{Network, RpcError};
 
 #[derive(Clone)]
 pub struct TxFeedback {
@@ -30,111 +34,301 @@ impl LatencyTracker {
     }
 }
 
+/// User-facing text for the RPC failures a sender can act on.
+pub fn describe_error(e: &RpcError) -> String {
+    if e.is_nonce_too_low() {
+        "Nonce too low: an earlier transaction already used it. Try again.".to_string()
+    } else if e.is_insufficient_funds() {
+        "Insufficient funds for amount + gas".to_string()
+    } else if e.is_rate_limited() {
+        "RPC rate limited. Wait a moment and retry.".to_string()
+    } else if let Some(reason) = e.revert_reason() {
+        format!("Reverted: {}", reason)
+    } else if let Some(data) = e.revert_data() {
+        format!("Reverted (data {})", data)
+    } else {
+        e.to_string()
+    }
+}
+
+thread_local! {
+    static LEGACY_NETWORKS: RefCell<Vec<Network>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Networks signed with legacy transactions (see `Settings::legacy_networks`).
+pub fn set_legacy_networks(networks: Vec<Network>) {
+    LEGACY_NETWORKS.with(|l| *l.borrow_mut() = networks);
+}
+
+fn signs_legacy(network: Network) -> bool {
+    LEGACY_NETWORKS.with(|l| l.borrow().contains(&network))
+}
+
+/// `tx` in the envelope `network` is signed with: EIP-1559 unless it is
+/// configured for legacy transactions. Those bid `price` (see
+/// `FeeSuggestion::price`), never more than the fee cap.
+fn envelope(network: Network, tx: &Eip1559TransactionRequest, price: Option<U256>) -> TypedTransaction {
+    if !signs_legacy(network) {
+        return tx.clone().into();
+    }
+    // Legacy transactions pay their gas price in full, so bidding the cap
+    // would pay all of its base fee headroom every time
+    TransactionRequest {
+        from: tx.from,
+        to: tx.to.clone(),
+        gas: tx.gas,
+        gas_price: price.into_iter().chain(tx.max_fee_per_gas).min(),
+        value: tx.value,
+        data: tx.data.clone(),
+        nonce: tx.nonce,
+        chain_id: tx.chain_id,
+    }.into()
+}
+
+/// Signs `typed` and returns the raw transaction and its hash, both 0x-hex.
+/// Type 2 envelopes are prefixed with their type byte, legacy ones are a bare RLP list.
+async fn sign_raw(wallet: &LocalWallet, typed: &TypedTransaction) -> Result<(String, String), String> {
+    let signature = wallet.sign_transaction(typed).await.map_err(|e| e.to_string())?;
+    let rlp = typed.rlp_signed(&signature);
+    Ok((format!("0x{}", hex::encode(&rlp)), format!("0x{}", hex::encode(ethers_core::utils::keccak256(&rlp)))))
+}
+
 pub async fn send_with_feedback(
     wallet: &LocalWallet,
-    tx: TransactionRequest,
+    network: Network,
+    tx: Eip1559TransactionRequest,
+    kind: TxKind,
     feedback: TxFeedback,
     conf_msg: &str
 ) -> Option<f64> { // Returns latency in ms
     let start = LatencyTracker::now();
+    feedback.set("Checking network...");
+
+    // 0. Never sign for a chain the endpoint does not serve
+    if let Err(e) = crate::provider::verify_chain(network).await {
+        feedback.set(&format!("Refusing to sign: {}", describe_error(&e)));
+        return None;
+    }
     feedback.set("Preparing...");
 
-    // 1. Get Nonce
-    let nonce_res = crate::rpc::get_transaction_count(&format!("{:?}", wallet.address()), Network::BaseSepolia).await;
-    let nonce = match nonce_res {
-        Ok(n) => n,
-        Err(e) => { feedback.set(&format!("Nonce Error: {}", e)); return None; }
+    // 1. Nonce, gas price and gas estimate in one batch
+    let from = wallet.address();
+    let mut tx = tx.clone();
+    tx.from = Some(from);
+    tx.chain_id = Some(ethers_core::types::U64::from(network.chain_id()));
+
+    let mut batch = crate::rpc::Batch::new();
+    let nonce_req = batch.transaction_count(&format!("{:?}", from));
+    // Price from the fee oracle at the user's speed unless the caller set one.
+    // Legacy envelopes always need the current price.
+    let legacy = signs_legacy(network);
+    let fee_req = (tx.max_fee_per_gas.is_none() || legacy).then(|| crate::fees::FeeRequest::queue(&mut batch));
+    let gas_req = tx.gas.is_none().then(|| batch.estimate_gas(serde_json::to_value(&tx).unwrap_or(serde_json::json!({}))));
+
+    feedback.set("Fetching Nonce & Gas...");
+    let mut resp = match batch.send(network).await {
+        Ok(r) => r,
+        Err(e) => { feedback.set(&format!("RPC Error: {}", describe_error(&e))); return None; }
     };
 
-    // 2. Sign
-    feedback.set("Signing...");
-    
-    // Ensure Nonce is set
-    let mut tx = tx.clone();
+    let nonce = match resp.get(nonce_req) {
+        Ok(n) => n,
+        Err(e) => { feedback.set(&format!("Nonce Error: {}", describe_error(&e))); return None; }
+    };
     tx.nonce = Some(U256::from(nonce));
-    tx.chain_id = Some(ethers_core::types::U64::from(84532)); // Hardcoded Base Sepolia
-
-    // Set Gas Price if not set (Fix "Transaction Underpriced")
-    if tx.gas_price.is_none() {
-        feedback.set("Fetching Gas Price...");
-        match crate::rpc::get_gas_price(Network::BaseSepolia).await {
-            Ok(gp) => {
-                // Add 20% buffer to ensure inclusion
-                let effective = gp + (gp / 5);
-                tx.gas_price = Some(U256::from(effective));
+
+    let mut price = None;
+    if let Some(req) = fee_req {
+        match req.redeem(&mut resp) {
+            Ok(fees) => {
+                let fee = fees.get(crate::fees::speed());
+                if tx.max_fee_per_gas.is_none() {
+                    tx.max_fee_per_gas = Some(fee.max_fee_per_gas.raw());
+                    tx.max_priority_fee_per_gas = Some(fee.max_priority_fee_per_gas.raw());
+                }
+                price = legacy.then(|| fee.price.raw());
             },
             Err(e) => {
-                 feedback.set(&format!("Gas Price Error: {}", e));
+                 feedback.set(&format!("Gas Price Error: {}", describe_error(&e)));
                  return None;
             }
         }
     }
 
-
-
-    // Estimate Gas if not set
-    if tx.gas.is_none() {
-        feedback.set("Estimating Gas...");
-        let tx_json = serde_json::to_value(&tx).unwrap_or(serde_json::json!({}));
-        match crate::rpc::estimate_gas(tx_json, Network::BaseSepolia).await {
+    if let Some(req) = gas_req {
+        match resp.get(req) {
             Ok(est) => {
                  // Add 20% buffer
                  let gas_limit = est + (est / 5);
                  tx.gas = Some(gas_limit);
             },
             Err(e) => {
-                feedback.set(&format!("Gas Est Error: {}", e));
+                feedback.set(&format!("Gas Est Error: {}", describe_error(&e)));
                 return None; 
             }
         }
-
     }
-    
-    // Actually, properly handling borrowing/mutability:
-    // We already signed at line 57. If we updated gas, that signature is invalid.
-    // Let's just re-sign if we estimated gas.
-    
-    // Sign
-    let signature = match wallet.sign_transaction(&tx.clone().into()).await {
-        Ok(s) => s,
+
+    // 2. Sign
+    feedback.set("Signing...");
+    let (rlp_hex, local_hash) = match sign_raw(wallet, &envelope(network, &tx, price)).await {
+        Ok(signed) => signed,
         Err(e) => { feedback.set(&format!("Sign Error: {}", e)); return None; }
     };
 
-    let rlp = tx.rlp_signed(&signature);
-    let rlp_hex = format!("0x{}", hex::encode(rlp));
-
-    // 3. Send
+    // 3. Send. The provider never re-sends on its own, so an unanswered
+    // request is resolved by asking whether the node knows the hash.
     feedback.set("Sending...");
-    match crate::rpc::send_raw_transaction(&rlp_hex, Network::BaseSepolia).await {
+    let sent = match crate::rpc::send_raw_transaction(&rlp_hex, network).await {
+        Err(e) if crate::provider::is_retryable(&e, true) => {
+            feedback.set("No reply from node. Checking whether the transaction arrived...");
+            match crate::rpc::get_transaction_by_hash(&local_hash, network).await {
+                Ok(known) if !known.is_null() => Ok(local_hash),
+                _ => Err(e),
+            }
+        },
+        other => other,
+    };
+
+    match sent {
         Ok(hash) => {
             feedback.set(&format!("Sent! Tx: {}. Waiting...", hash));
+            let to = tx.to.as_ref().and_then(|t| t.as_address()).map(|a| format!("{:?}", a));
+            // Filed under the signer, even if another wallet is active by the time the receipt lands
+            let owner = format!("{:?}", from);
+            crate::history::record_sent(network, &owner, TxRecord::sent(hash.clone(), kind, owner.clone(), to, tx.value)).await;
             
             // 4. Poll for Receipt
-            let mut attempts = 0;
+            let sent_at = LatencyTracker::now();
             loop {
-                gloo_timers::future::TimeoutFuture::new(2000).await; // 2s
-                let receipt = crate::rpc::get_transaction_receipt(&hash, Network::BaseSepolia).await;
-                if let Ok(r) = receipt {
-                    if !r.is_null() {
+                // Check as soon as a block lands; every 2s without a subscription
+                crate::rpc::next_head(2000).await;
+                match crate::rpc::get_transaction_receipt(&hash, network).await {
+                    Ok(r) if r.is_null() => {},
+                    Ok(r) => {
                         if r["status"].as_str() == Some("0x1") {
                             let end = LatencyTracker::now();
                             let latency = end - start;
+                            crate::history::record_receipt(network, &owner, &hash, &r, Some(latency)).await;
                             feedback.set(&format!("{} ({:.0}ms)", conf_msg, latency));
                             return Some(latency);
                         } else {
+                            crate::history::record_receipt(network, &owner, &hash, &r, None).await;
                             feedback.set("Failed on-chain.");
                             break;
                         }
+                    },
+                    // Already retried by the provider; keep polling
+                    Err(e) if crate::provider::is_retryable(&e, true) => {
+                        feedback.set(&format!("Sent! Tx: {}. Waiting... ({})", hash, e.label()));
+                    },
+                    Err(e) => {
+                        feedback.set(&format!("Receipt Error: {}", describe_error(&e)));
+                        break;
                     }
                 }
-                attempts += 1;
-                if attempts > 30 { // 60s
+                // Time based, since head notifications arrive once per block
+                if LatencyTracker::now() - sent_at > 60_000.0 {
                     feedback.set("Timeout.");
                     break;
                 }
             }
         },
-        Err(e) => feedback.set(&format!("Send Error: {}", e)),
+        Err(e) => feedback.set(&format!("Send Error: {}", describe_error(&e))),
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{set_transport, AnyTransport, MockTransport};
+    use ethers_core::types::{Address, U64};
+    use ethers_core::utils::rlp::Rlp;
+    use futures::executor::block_on;
+    use serde_json::{json, Value};
+
+    fn wallet() -> LocalWallet {
+        // Anvil's first dev account
+        "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80".parse().unwrap()
+    }
+
+    fn transfer(network: Network) -> Eip1559TransactionRequest {
+        Eip1559TransactionRequest::new()
+            .from(wallet().address())
+            .to(Address::repeat_byte(0x11))
+            .value(U256::exp10(15))
+            .nonce(4)
+            .gas(21_000)
+            .max_fee_per_gas(2_000_000_000u64)
+            .max_priority_fee_per_gas(1_000_000u64)
+            .chain_id(network.chain_id())
+    }
+
+    /// Answers sends with the hash a node derives: keccak256 of the raw envelope.
+    fn hashing_node() -> MockTransport {
+        let mock = MockTransport::new();
+        mock.respond_with("eth_sendRawTransaction", |params: &Value| {
+            let raw = hex::decode(params[0].as_str().unwrap_or_default().trim_start_matches("0x")).unwrap_or_default();
+            json!(format!("0x{}", hex::encode(ethers_core::utils::keccak256(raw))))
+        });
+        set_transport(AnyTransport::Mock(mock.clone()));
+        mock
+    }
+
+    fn decode(raw: &str) -> (TypedTransaction, Address) {
+        let bytes = hex::decode(raw.trim_start_matches("0x")).unwrap();
+        let (typed, signature) = TypedTransaction::decode_signed(&Rlp::new(&bytes)).unwrap();
+        let signer = signature.recover(typed.sighash()).unwrap();
+        (typed, signer)
+    }
+
+    #[test]
+    fn signs_type_2_envelopes_by_default() {
+        let network = Network::BaseSepolia;
+        let mock = hashing_node();
+        let (raw, local_hash) = block_on(sign_raw(&wallet(), &envelope(network, &transfer(network), None))).unwrap();
+        assert!(raw.starts_with("0x02"), "{}", raw);
+
+        let (typed, signer) = decode(&raw);
+        assert!(matches!(typed, TypedTransaction::Eip1559(_)));
+        assert_eq!(signer, wallet().address());
+        assert_eq!(typed.chain_id(), Some(U64::from(84532)));
+        assert_eq!(typed.nonce(), Some(&U256::from(4)));
+
+        // The hash we file history under is the one the node reports
+        let node_hash = block_on(crate::rpc::send_raw_transaction(&raw, network)).unwrap();
+        assert_eq!(node_hash, local_hash);
+        assert_eq!(mock.requests()[0]["params"][0], json!(raw));
+    }
+
+    #[test]
+    fn signs_legacy_envelopes_where_configured() {
+        let network = Network::Anvil;
+        hashing_node();
+        set_legacy_networks(vec![Network::Anvil]);
+        // Other networks keep EIP-1559
+        assert!(matches!(envelope(Network::BaseSepolia, &transfer(Network::BaseSepolia), None), TypedTransaction::Eip1559(_)));
+
+        let price = Some(U256::from(1_200_000_000u64));
+        let (raw, local_hash) = block_on(sign_raw(&wallet(), &envelope(network, &transfer(network), price))).unwrap();
+        // A bare RLP list, no type byte
+        assert!(hex::decode(&raw[2..4]).unwrap()[0] >= 0xc0, "{}", raw);
+
+        let (typed, signer) = decode(&raw);
+        let TypedTransaction::Legacy(legacy) = &typed else { panic!("not legacy: {:?}", typed) };
+        // The current price, not the 2 gwei cap
+        assert_eq!(legacy.gas_price, price);
+        assert_eq!(legacy.chain_id, Some(U64::from(31337)));
+        assert_eq!(signer, wallet().address());
+        assert_eq!(block_on(crate::rpc::send_raw_transaction(&raw, network)).unwrap(), local_hash);
+
+        // Never above the cap, and the cap when there is no price
+        let gas_price = |price: Option<u64>| envelope(network, &transfer(network), price.map(U256::from)).gas_price();
+        assert_eq!(gas_price(Some(3_000_000_000)), Some(U256::from(2_000_000_000u64)));
+        assert_eq!(gas_price(None), Some(U256::from(2_000_000_000u64)));
+
+        set_legacy_networks(Vec::new());
+        assert!(matches!(envelope(network, &transfer(network), price), TypedTransaction::Eip1559(_)));
+    }
+}
@@ -0,0 +1,250 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+
+use crate::rpc::RpcError;
+
+/// Carries one JSON-RPC body (a single request or a batch) to an endpoint
+/// and returns the parsed response body. Retries, failover and quorum live
+/// above this, in `provider`.
+#[allow(async_fn_in_trait)]
+pub trait Transport {
+    async fn post(&self, url: &str, body: &Value) -> Result<Value, RpcError>;
+}
+
+/// Runtime-selected transport, since async trait methods are not object safe.
+#[derive(Clone)]
+pub enum AnyTransport {
+    Gloo(GlooTransport),
+    #[cfg(not(target_arch = "wasm32"))]
+    Reqwest(ReqwestTransport),
+    Mock(MockTransport),
+}
+
+impl AnyTransport {
+    /// gloo-net in the browser, reqwest on native builds.
+    pub fn detect() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        return AnyTransport::Reqwest(ReqwestTransport::default());
+        #[cfg(target_arch = "wasm32")]
+        return AnyTransport::Gloo(GlooTransport);
+    }
+}
+
+impl Transport for AnyTransport {
+    async fn post(&self, url: &str, body: &Value) -> Result<Value, RpcError> {
+        match self {
+            AnyTransport::Gloo(t) => t.post(url, body).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            AnyTransport::Reqwest(t) => t.post(url, body).await,
+            AnyTransport::Mock(t) => t.post(url, body).await,
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<AnyTransport>> = const { RefCell::new(None) };
+}
+
+/// Routes every RPC request from now on through `transport`.
+pub fn set_transport(transport: AnyTransport) {
+    CURRENT.with(|c| *c.borrow_mut() = Some(transport));
+}
+
+/// The transport set with `set_transport`, or the platform default.
+pub fn current() -> AnyTransport {
+    CURRENT.with(|c| c.borrow_mut().get_or_insert_with(AnyTransport::detect).clone())
+}
+
+/// Wall clock in milliseconds, on either platform.
+pub fn now_ms() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    return js_sys::Date::now();
+    #[cfg(not(target_arch = "wasm32"))]
+    return std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0);
+}
+
+/// Waits `ms` on the browser's timers, or tokio's natively.
+pub async fn sleep(ms: u32) {
+    #[cfg(target_arch = "wasm32")]
+    gloo_timers::future::TimeoutFuture::new(ms).await;
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(std::time::Duration::from_millis(ms as u64)).await;
+}
+
+// Only the delay-seconds form; HTTP-date values fall back to backoff
+fn retry_after_ms(header: Option<&str>) -> Option<u64> {
+    header.and_then(|v| v.trim().parse::<u64>().ok()).map(|secs| secs * 1000)
+}
+
+/// `fetch` through gloo-net. Browser only.
+#[derive(Clone, Copy, Default)]
+pub struct GlooTransport;
+
+impl Transport for GlooTransport {
+    async fn post(&self, url: &str, body: &Value) -> Result<Value, RpcError> {
+        let resp = gloo_net::http::Request::post(url)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .map_err(|e| RpcError::Transport(e.to_string()))?
+            .send()
+            .await
+            .map_err(|e| RpcError::Transport(e.to_string()))?;
+
+        if !resp.ok() {
+            let retry_after_ms = retry_after_ms(resp.headers().get("Retry-After").as_deref());
+            let body = resp.text().await.unwrap_or_default();
+            return Err(RpcError::Http { status: resp.status(), body, retry_after_ms });
+        }
+        resp.json().await.map_err(|e| RpcError::Decode(e.to_string()))
+    }
+}
+
+/// reqwest, for the native debug tools.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Transport for ReqwestTransport {
+    async fn post(&self, url: &str, body: &Value) -> Result<Value, RpcError> {
+        let resp = self.client.post(url)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| RpcError::Transport(e.to_string()))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let retry_after_ms = retry_after_ms(resp.headers().get(reqwest::header::RETRY_AFTER).and_then(|v| v.to_str().ok()));
+            let body = resp.text().await.unwrap_or_default();
+            return Err(RpcError::Http { status: status.as_u16(), body, retry_after_ms });
+        }
+        resp.json().await.map_err(|e| RpcError::Decode(e.to_string()))
+    }
+}
+
+/// In-process endpoint answering from a script, for tests and offline runs.
+/// Clones share the script and the request log.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Rc<RefCell<MockState>>,
+}
+
+/// A scripted reply: fixed, or computed from the request's params.
+#[derive(Clone)]
+enum Reply {
+    Fixed(Value),
+    Computed(Rc<dyn Fn(&Value) -> Value>),
+}
+
+#[derive(Default)]
+struct MockState {
+    /// Replies per (url, method), in order; the last one keeps answering.
+    /// A `None` url answers for every endpoint without its own script.
+    script: HashMap<(Option<String>, String), VecDeque<Reply>>,
+    /// Endpoints that fail with a transport error after receiving the body
+    unreachable: Vec<String>,
+    requests: Vec<(String, Value)>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, url: Option<&str>, method: &str, reply: Reply) -> &Self {
+        let key = (url.map(str::to_string), method.to_string());
+        self.state.borrow_mut().script.entry(key).or_default().push_back(reply);
+        self
+    }
+
+    /// Queues `result` as the next answer to `method`.
+    pub fn respond(&self, method: &str, result: Value) -> &Self {
+        self.push(None, method, Reply::Fixed(json!({ "result": result })))
+    }
+
+    /// Answers `method` with `result(params)` from now on, like a node
+    /// deriving its reply from the request.
+    pub fn respond_with(&self, method: &str, result: impl Fn(&Value) -> Value + 'static) -> &Self {
+        self.push(None, method, Reply::Computed(Rc::new(move |params| json!({ "result": result(params) }))))
+    }
+
+    /// Like `respond`, for requests to `url` only.
+    pub fn respond_at(&self, url: &str, method: &str, result: Value) -> &Self {
+        self.push(Some(url), method, Reply::Fixed(json!({ "result": result })))
+    }
+
+    /// Queues a JSON-RPC error as the next answer to `method`.
+    pub fn fail(&self, method: &str, code: i64, message: &str) -> &Self {
+        self.push(None, method, Reply::Fixed(json!({ "error": { "code": code, "message": message } })))
+    }
+
+    /// Like `fail`, for requests to `url` only.
+    pub fn fail_at(&self, url: &str, method: &str, code: i64, message: &str) -> &Self {
+        self.push(Some(url), method, Reply::Fixed(json!({ "error": { "code": code, "message": message } })))
+    }
+
+    /// Queues a revert carrying `data` as the next answer to `method`.
+    pub fn revert(&self, method: &str, data: &str) -> &Self {
+        self.push(None, method, Reply::Fixed(json!({ "error": { "code": 3, "message": "execution reverted", "data": data } })))
+    }
+
+    /// Makes every request to `url` fail as if the connection dropped.
+    /// The requests are still logged: the node may have received them.
+    pub fn unreachable(&self, url: &str) -> &Self {
+        self.state.borrow_mut().unreachable.push(url.to_string());
+        self
+    }
+
+    /// Every request received so far, batches flattened.
+    pub fn requests(&self) -> Vec<Value> {
+        self.state.borrow().requests.iter().map(|(_, r)| r.clone()).collect()
+    }
+
+    /// Requests received by `url`, batches flattened.
+    pub fn requests_to(&self, url: &str) -> Vec<Value> {
+        self.state.borrow().requests.iter().filter(|(u, _)| u == url).map(|(_, r)| r.clone()).collect()
+    }
+
+    fn answer(&self, url: &str, request: &Value) -> Value {
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        let mut state = self.state.borrow_mut();
+        state.requests.push((url.to_string(), request.clone()));
+        let own = (Some(url.to_string()), method.clone());
+        let key = if state.script.contains_key(&own) { own } else { (None, method.clone()) };
+        let reply = state.script.get_mut(&key).and_then(|replies| {
+            if replies.len() > 1 { replies.pop_front() } else { replies.front().cloned() }
+        });
+        drop(state);
+        let reply = reply.map(|reply| match reply {
+            Reply::Fixed(value) => value,
+            Reply::Computed(f) => f(&request["params"]),
+        });
+        let mut reply = reply.unwrap_or_else(|| json!({ "error": { "code": -32601, "message": format!("{} is not scripted", method) } }));
+        reply["jsonrpc"] = json!("2.0");
+        reply["id"] = request["id"].clone();
+        reply
+    }
+}
+
+impl Transport for MockTransport {
+    async fn post(&self, url: &str, body: &Value) -> Result<Value, RpcError> {
+        let reply = match body {
+            // Reversed, since JSON-RPC lets batch responses come in any order
+            Value::Array(requests) => Value::Array(requests.iter().map(|r| self.answer(url, r)).rev().collect()),
+            request => self.answer(url, request),
+        };
+        if self.state.borrow().unreachable.iter().any(|u| u == url) {
+            return Err(RpcError::Transport(format!("{} is unreachable", url)));
+        }
+        Ok(reply)
+    }
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use crate::storage::StorageBackend;
+use crate::wallet::Keystore;
+
+pub const VAULT_KEY: &str = "diamond_wallet_vault";
+/// Pre-vault storage slot holding a single keystore.
+pub const LEGACY_KEYSTORE_KEY: &str = "diamond_wallet_keystore";
+
+/// All wallets known to this browser.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Vault {
+    pub wallets: Vec<VaultEntry>,
+    /// Index into `wallets`; None while onboarding a new wallet
+    pub active: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct VaultEntry {
+    pub name: String,
+    pub keystore: Keystore,
+}
+
+impl Vault {
+    /// Loads the vault, migrating each keystore to the current schema.
+    pub async fn load(store: &impl StorageBackend) -> Result<Vault, String> {
+        let Some(mut value) = crate::storage::get::<serde_json::Value>(store, VAULT_KEY).await? else {
+            return Ok(Vault::default());
+        };
+        if let Some(wallets) = value.get_mut("wallets").and_then(|w| w.as_array_mut()) {
+            for entry in wallets {
+                let name = entry.get("name").and_then(|n| n.as_str()).unwrap_or("?").to_string();
+                let keystore = entry.get_mut("keystore").ok_or(format!("Wallet '{}' has no keystore", name))?;
+                // Vault entries were always encrypted, so no plaintext key can surface here
+                crate::schema::migrate_keystore(keystore).map_err(|e| format!("Wallet '{}': {}", name, e))?;
+            }
+        }
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+
+    pub async fn save(&self, store: &impl StorageBackend) -> Result<(), String> {
+        crate::storage::set(store, VAULT_KEY, self).await
+    }
+
+    pub fn active_keystore(&self) -> Option<Keystore> {
+        self.active.and_then(|i| self.wallets.get(i)).map(|e| e.keystore.clone())
+    }
+
+    /// Stores `k` in the active slot, adding a new entry when none is active.
+    pub fn upsert_active(&mut self, name: &str, k: Keystore) {
+        match self.active.and_then(|i| self.wallets.get_mut(i)) {
+            Some(entry) => entry.keystore = k,
+            None => {
+                let name = if name.trim().is_empty() {
+                    format!("Wallet {}", self.wallets.len() + 1)
+                } else {
+                    name.trim().to_string()
+                };
+                self.wallets.push(VaultEntry { name, keystore: k });
+                self.active = Some(self.wallets.len() - 1);
+            }
+        }
+    }
+
+    /// Removes a wallet. If it was active, the first remaining one becomes active.
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.wallets.len() { return; }
+        self.wallets.remove(index);
+        self.active = match self.active {
+            Some(a) if a == index => if self.wallets.is_empty() { None } else { Some(0) },
+            Some(a) if a > index => Some(a - 1),
+            other => other,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryBackend;
+    use crate::wallet::KeySource;
+    use futures::executor::block_on;
+    use serde_json::json;
+
+    fn watch_only(address: &str) -> Keystore {
+        Keystore { address: address.to_string(), source: KeySource::WatchOnly, ..Keystore::default() }
+    }
+
+    fn addresses(vault: &Vault) -> Vec<&str> {
+        vault.wallets.iter().map(|e| e.keystore.address.as_str()).collect()
+    }
+
+    #[test]
+    fn load_without_a_vault_is_empty() {
+        let vault = block_on(Vault::load(&MemoryBackend::default())).unwrap();
+        assert!(vault.wallets.is_empty());
+        assert_eq!(vault.active, None);
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let store = MemoryBackend::default();
+        let mut vault = Vault::default();
+        vault.upsert_active("Main", watch_only("0x01"));
+        vault.active = None;
+        vault.upsert_active("", watch_only("0x02"));
+        block_on(vault.save(&store)).unwrap();
+
+        let loaded = block_on(Vault::load(&store)).unwrap();
+        assert_eq!(addresses(&loaded), ["0x01", "0x02"]);
+        assert_eq!(loaded.wallets[0].name, "Main");
+        assert_eq!(loaded.wallets[1].name, "Wallet 2");
+        assert_eq!(loaded.active, Some(1));
+        assert_eq!(loaded.active_keystore().unwrap().address, "0x02");
+    }
+
+    #[test]
+    fn load_migrates_unversioned_entries() {
+        let store = MemoryBackend::default();
+        let stored = json!({
+            "wallets": [{ "name": "Old", "keystore": { "address": "0x01", "crypto": null, "source": { "type": "watch_only" } } }],
+            "active": 0
+        });
+        block_on(crate::storage::set(&store, VAULT_KEY, &stored)).unwrap();
+
+        let vault = block_on(Vault::load(&store)).unwrap();
+        let keystore = vault.active_keystore().unwrap();
+        assert_eq!(keystore.schema, crate::schema::KEYSTORE_SCHEMA);
+        assert!(keystore.source == KeySource::WatchOnly);
+        assert!(keystore.accounts.is_empty());
+    }
+
+    #[test]
+    fn load_names_the_wallet_a_migration_rejects() {
+        let store = MemoryBackend::default();
+        let stored = json!({
+            "wallets": [{ "name": "Future", "keystore": { "schema": 99, "address": "0x01" } }],
+            "active": 0
+        });
+        block_on(crate::storage::set(&store, VAULT_KEY, &stored)).unwrap();
+
+        let err = block_on(Vault::load(&store)).err().unwrap();
+        assert!(err.starts_with("Wallet 'Future': Keystore schema v99 is newer"), "{}", err);
+    }
+
+    #[test]
+    fn upsert_active_replaces_the_active_keystore() {
+        let mut vault = Vault::default();
+        vault.upsert_active("Main", watch_only("0x01"));
+        vault.upsert_active("ignored", watch_only("0x02"));
+        assert_eq!(addresses(&vault), ["0x02"]);
+        assert_eq!(vault.wallets[0].name, "Main");
+        assert_eq!(vault.active, Some(0));
+    }
+
+    #[test]
+    fn remove_keeps_the_active_wallet_selected() {
+        let mut vault = Vault::default();
+        for address in ["0x01", "0x02", "0x03"] {
+            vault.active = None;
+            vault.upsert_active("", watch_only(address));
+        }
+
+        // Removing one before the active wallet shifts its index
+        vault.remove(0);
+        assert_eq!(addresses(&vault), ["0x02", "0x03"]);
+        assert_eq!(vault.active_keystore().unwrap().address, "0x03");
+
+        // Out of range is a no-op
+        vault.remove(5);
+        assert_eq!(vault.wallets.len(), 2);
+
+        // Removing the active wallet falls back to the first
+        vault.remove(1);
+        assert_eq!(vault.active, Some(0));
+        assert_eq!(vault.active_keystore().unwrap().address, "0x02");
+
+        vault.remove(0);
+        assert_eq!(vault.active, None);
+    }
+}
@@ -1,33 +1,353 @@
 use leptos::*;
-use gloo_storage::{LocalStorage, Storage};
 use serde::{Deserialize, Serialize};
-use crate::rpc::{Network, get_balance};
-use rand::rngs::SmallRng;
-use rand::{RngCore, SeedableRng};
+use crate::rpc::{Batch, Network};
+use crate::amount::Amount;
+use crate::history::TxKind;
+use crate::crypto::CryptoJson;
+use crate::vault::{Vault, LEGACY_KEYSTORE_KEY};
+use crate::storage::{Backend, StorageBackend};
+use crate::session::Session;
+use crate::settings::Settings;
+use crate::schema::{migrate_keystore, KEYSTORE_SCHEMA};
+use crate::shamir::{SecretKind, Share};
+use rand::rngs::OsRng;
+use rand::RngCore;
 use alloy_primitives::hex;
 use wasm_bindgen::JsCast;
 
-#[derive(Serialize, Deserialize, Clone, Default)]
+/// Keystore as persisted in the vault and backup files.
+/// The private key is only ever stored encrypted (`crypto`).
+/// `address` and `smart_account` always describe the active signer.
+/// Stored copies must pass through `schema::migrate_keystore` before parsing.
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Keystore {
-    pub private_key: String,
+    /// On-disk format version (see `schema::KEYSTORE_SCHEMA`)
+    pub schema: u32,
     pub address: String,
     pub smart_account: Option<String>,
+    pub crypto: Option<CryptoJson>,
+    #[serde(default)]
+    pub source: KeySource,
+    /// HD accounts derived from the phrase (empty for single-key keystores)
+    #[serde(default)]
+    pub accounts: Vec<HdAccount>,
+    #[serde(default)]
+    pub active_account: usize,
+}
+
+/// One BIP-44 signer derived from the keystore's phrase.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct HdAccount {
+    pub path: String,
+    pub address: String,
+    pub smart_account: Option<String>,
+}
+
+impl Default for Keystore {
+    fn default() -> Self {
+        Self {
+            schema: KEYSTORE_SCHEMA,
+            address: String::new(),
+            smart_account: None,
+            crypto: None,
+            source: KeySource::default(),
+            accounts: Vec::new(),
+            active_account: 0,
+        }
+    }
+}
+
+impl Keystore {
+    pub fn is_watch_only(&self) -> bool {
+        self.source == KeySource::WatchOnly
+    }
+
+    /// Derivation path of the active signer, for mnemonic keystores.
+    pub fn active_path(&self) -> Option<String> {
+        match &self.source {
+            KeySource::Random | KeySource::WatchOnly => None,
+            KeySource::Mnemonic { path } => Some(
+                self.accounts.get(self.active_account).map(|a| a.path.clone()).unwrap_or(path.clone())
+            ),
+        }
+    }
+
+    /// Sets the active signer's smart account, keeping its HD entry in sync.
+    pub fn set_smart_account(&mut self, smart_account: Option<String>) {
+        if let Some(acc) = self.accounts.get_mut(self.active_account) {
+            acc.smart_account = smart_account.clone();
+        }
+        self.smart_account = smart_account;
+    }
+
+    /// Makes HD account `index` the active signer.
+    pub fn select_account(&mut self, index: usize) {
+        if let Some(acc) = self.accounts.get(index) {
+            self.active_account = index;
+            self.address = acc.address.clone();
+            self.smart_account = acc.smart_account.clone();
+        }
+    }
+}
+
+/// What the encrypted secret in a `Keystore` is.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeySource {
+    /// A raw 32-byte private key.
+    #[default]
+    Random,
+    /// A BIP-39 phrase; the first signer is derived at `path`.
+    Mnemonic { path: String },
+    /// No key at all; the address is only monitored.
+    WatchOnly,
+}
+
+/// Turns a decrypted secret into the active signer it describes.
+fn wallet_from_secret(k: &Keystore, secret: &[u8]) -> Result<ethers_signers::LocalWallet, String> {
+    match k.active_path() {
+        None => ethers_signers::LocalWallet::from_bytes(secret).map_err(|e| e.to_string()),
+        Some(path) => {
+            let phrase = std::str::from_utf8(secret).map_err(|e| e.to_string())?;
+            crate::mnemonic::derive_wallet(phrase, &path)
+        }
+    }
+}
+
+/// Parses a stored or imported keystore of any known schema. Legacy plaintext
+/// keystores are returned together with their key so the user can encrypt them.
+fn parse_keystore(json: &str) -> Result<(Keystore, Option<String>), String> {
+    let mut value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let legacy_key = migrate_keystore(&mut value)?;
+    let k: Keystore = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    Ok((k, legacy_key))
+}
+
+/// A balance as fetched: `None` while loading, `Err` with a short label on failure.
+type Balance = Option<Result<Amount, String>>;
+
+fn balance_text(balance: Balance) -> String {
+    match balance {
+        None => "...".to_string(),
+        Some(Ok(amount)) => format!("{} ETH", amount.format_locale(4)),
+        Some(Err(label)) => label,
+    }
+}
+
+/// Fetches a balance past the RPC cache, which may still hold it from before a drain.
+async fn fresh_balance(net: Network, address: &str) -> Result<Amount, String> {
+    let mut batch = Batch::new();
+    let req = batch.balance(address);
+    let mut resp = batch.send(net).await.map_err(|e| crate::transactions::describe_error(&e))?;
+    resp.get(req).map_err(|e| crate::transactions::describe_error(&e))
+}
+
+/// Sends everything a smart account holds to `to`, paid for by its owner `wallet`.
+/// Fails unless the account is verifiably empty afterwards.
+async fn drain_tba(net: Network, wallet: &ethers_signers::LocalWallet, tba: &str, to: ethers_core::types::Address, feedback: crate::transactions::TxFeedback) -> Result<(), String> {
+    use ethers_core::abi::{encode, Token};
+    use ethers_core::types::{Eip1559TransactionRequest, U256};
+
+    feedback.set("Checking TBA balance...");
+    let balance = fresh_balance(net, tba).await.map_err(|e| format!("TBA {tba} balance unknown ({e})"))?;
+    if balance.is_zero() {
+        return Ok(());
+    }
+    let tba_addr = tba.parse::<ethers_core::types::Address>().map_err(|e| e.to_string())?;
+    let mut data = hex::decode("24857bd4").unwrap();
+    data.extend(encode(&[
+        Token::Address(to),
+        Token::Uint(balance.raw()),
+        Token::Bytes(vec![]),
+        Token::Uint(U256::from(0u8)),
+    ]));
+    let tx = Eip1559TransactionRequest::new()
+        .to(tba_addr)
+        .value(0)
+        .data(data)
+        .gas(U256::from(200000u64)); // TBA overhead
+
+    feedback.set("Draining TBA...");
+    if crate::transactions::send_with_feedback(wallet, net, tx, TxKind::Drain, feedback, "TBA Drained!").await.is_none() {
+        return Err(format!("draining TBA {tba} failed"));
+    }
+    match fresh_balance(net, tba).await {
+        Ok(left) if left.is_zero() => Ok(()),
+        Ok(left) => Err(format!("TBA {tba} still holds {left} ETH")),
+        Err(e) => Err(format!("TBA {tba} balance unknown ({e})")),
+    }
+}
+
+/// Sends a signer's balance, less the fee, to `to`. What stays behind must
+/// be less than moving it would cost; anything more is reported.
+async fn drain_signer(net: Network, wallet: &ethers_signers::LocalWallet, to: ethers_core::types::Address, feedback: crate::transactions::TxFeedback) -> Result<(), String> {
+    use ethers_core::types::{Eip1559TransactionRequest, U256};
+    use ethers_signers::Signer;
+
+    const GAS_LIMIT: u64 = 21000;
+    let address = format!("{:?}", wallet.address());
+    feedback.set("Checking Signer balance...");
+    let balance = fresh_balance(net, &address).await.map_err(|e| format!("{address} balance unknown ({e})"))?;
+    let fees = crate::fees::suggest(net).await.map_err(|e| format!("no fee estimate ({})", crate::transactions::describe_error(&e)))?.sweep();
+    let cost = fees.max_fee_per_gas.checked_mul(GAS_LIMIT).ok_or("fee overflow")?;
+    // Dust below the fee cannot be moved at all
+    let Some(send_amount) = balance.checked_sub(cost).filter(|a| !a.is_zero()) else {
+        return Ok(());
+    };
+    let tx = Eip1559TransactionRequest::new()
+        .to(to)
+        .value(send_amount.raw())
+        .gas(U256::from(GAS_LIMIT))
+        .max_fee_per_gas(fees.max_fee_per_gas.raw())
+        .max_priority_fee_per_gas(fees.max_priority_fee_per_gas.raw());
+
+    feedback.set("Draining Signer...");
+    if crate::transactions::send_with_feedback(wallet, net, tx, TxKind::Drain, feedback, "Signer Drained!").await.is_none() {
+        return Err(format!("draining {address} failed"));
+    }
+    match fresh_balance(net, &address).await {
+        Ok(left) if left <= cost => Ok(()),
+        Ok(left) => Err(format!("{address} still holds {left} ETH")),
+        Err(e) => Err(format!("{address} balance unknown ({e})")),
+    }
+}
+
+/// Drains every signer of `k` (each HD account, or the single key) and their
+/// smart accounts to the network's deployer. Returns why funds may remain on
+/// chain; empty only when every account was verifiably emptied.
+async fn drain_all(net: Network, k: &Keystore, session: Option<Session>, feedback: crate::transactions::TxFeedback) -> Vec<String> {
+    use ethers_signers::Signer;
+
+    let Some(session) = session else {
+        return vec!["the wallet is locked, so nothing was drained".to_string()];
+    };
+    let Some(deployer) = net.contracts().deployer else {
+        return vec![format!("{} has no drain target", net.name())];
+    };
+    let to: ethers_core::types::Address = deployer.parse().unwrap();
+
+    let signers: Vec<(Option<String>, String, Option<String>)> = match &k.source {
+        KeySource::Mnemonic { .. } if !k.accounts.is_empty() => k.accounts.iter()
+            .map(|a| (Some(a.path.clone()), a.address.clone(), a.smart_account.clone()))
+            .collect(),
+        _ => vec![(k.active_path(), k.address.clone(), k.smart_account.clone())],
+    };
+
+    let mut blockers = Vec::new();
+    for (path, address, smart_account) in signers {
+        let wallet = match path {
+            None => Ok(session.wallet()),
+            Some(path) => session.phrase().ok_or_else(|| "phrase unavailable".to_string())
+                .and_then(|phrase| crate::mnemonic::derive_wallet(phrase, &path)),
+        };
+        let wallet = match wallet {
+            Ok(w) if format!("{:?}", w.address()).eq_ignore_ascii_case(&address) => w.with_chain_id(net.chain_id()),
+            _ => { blockers.push(format!("no key for {address}")); continue; }
+        };
+        if let Some(tba) = smart_account {
+            if let Err(e) = drain_tba(net, &wallet, &tba, to, feedback.clone()).await {
+                blockers.push(e);
+            }
+        }
+        if let Err(e) = drain_signer(net, &wallet, to, feedback.clone()).await {
+            blockers.push(e);
+        }
+    }
+    blockers
+}
+
+/// Outcome of verified mode for one address: what was proven, or why not.
+/// `None` while the proof is being checked.
+type ProofMark = Option<Result<String, String>>;
+
+fn proof_mark_view(mark: ProofMark) -> impl IntoView {
+    match mark {
+        None => view! { <span class="tiny-text" style="color:#888;">"verifying..."</span> },
+        Some(Ok(text)) => view! { <span class="tiny-text" style="color:#4CAF50;">{text}</span> },
+        Some(Err(reason)) => view! { <span class="tiny-text" style="color:#ff9800;">{format!("⚠ unverified: {}", reason)}</span> },
+    }
+}
+
+/// Token of the identity NFT whose ERC-6551 account is the wallet's TBA.
+const IDENTITY_TOKEN_ID: u64 = 1;
+/// Constructor of the ERC-6551 v3 proxy; the runtime code follows it.
+const TBA_CONSTRUCTOR: &str = "3d60ad80600a3d3981f3";
+
+/// Salt and creation code of the ERC-6551 v3 account for `token_id` of `nft`.
+fn tba_creation_code(implementation: &str, nft: &str, chain_id: u64, token_id: u64) -> ([u8; 32], Vec<u8>) {
+    use ethers_core::abi::{encode, Token};
+    use ethers_core::utils::keccak256;
+    use ethers_core::types::U256;
+
+    let nft: ethers_core::types::Address = nft.parse().unwrap_or_default();
+    let salt = keccak256(encode(&[
+        Token::Uint(U256::from(chain_id)),
+        Token::Address(nft),
+        Token::Uint(U256::from(token_id))
+    ]));
+
+    let mut creation_code = Vec::new();
+    creation_code.extend(hex::decode(TBA_CONSTRUCTOR).unwrap());
+    creation_code.extend(hex::decode("363d3d373d3d3d363d73").unwrap());
+    creation_code.extend(hex::decode(implementation.trim_start_matches("0x")).unwrap_or_default());
+    creation_code.extend(hex::decode("5af43d82803e903d91602b57fd5bf3").unwrap());
+    // Footer Data
+    creation_code.extend(encode(&[
+        Token::FixedBytes(salt.to_vec()),
+        Token::Uint(U256::from(chain_id)),
+        Token::Address(nft),
+        Token::Uint(U256::from(token_id))
+    ]));
+    (salt, creation_code)
+}
+
+fn gwei_text(wei: Amount) -> String {
+    // Same raw value, read with 9 decimals
+    Amount::new(wei.raw(), 9).map(|gwei| gwei.format_locale(3)).unwrap_or_default()
 }
 
 #[component]
 pub fn WalletView() -> impl IntoView {
+    let (vault, set_vault) = create_signal(Vault::default());
+    // Active wallet's keystore (default while onboarding)
     let (keystore, set_keystore) = create_signal(Keystore::default());
     let (status, set_status) = create_signal("Ready".to_string());
+
+    // Decrypted key material for this session (None while locked)
+    let (session, set_session) = create_signal(None::<Session>);
+    let (settings, set_settings) = create_signal(Settings::default());
+    let network = move || settings.get_untracked().network;
+    // Timestamp (ms) of the last user interaction, for auto-lock
+    let last_activity = store_value(js_sys::Date::now());
+    let (passphrase, set_passphrase) = create_signal("".to_string());
+
+    // Mnemonic Onboarding State
+    let (word_count, set_word_count) = create_signal(12usize);
+    let (pending_mnemonic, set_pending_mnemonic) = create_signal(None::<String>);
+    let (confirm_indices, set_confirm_indices) = create_signal(Vec::<usize>::new());
+    let (confirm_input, set_confirm_input) = create_signal("".to_string());
+    let (show_mnemonic_import, set_show_mnemonic_import) = create_signal(false);
+    let (mnemonic_input, set_mnemonic_input) = create_signal("".to_string());
     
     // Latency Tracking
     let (_last_latency, set_last_latency) = create_signal(None::<f64>);
     
     // Balances
-    let (bal_sepolia, set_bal_sepolia) = create_signal("...".to_string());
+    let (signer_balance, set_signer_balance) = create_signal(None::<Result<Amount, String>>);
 
     
     // TBA Balances
-    let (tba_bal_sepolia, set_tba_bal_sepolia) = create_signal("...".to_string());
+    let (tba_balance, set_tba_balance) = create_signal(None::<Result<Amount, String>>);
+
+    // Verified-mode marks for the signer and TBA balances
+    let (signer_proof, set_signer_proof) = create_signal(None::<Result<String, String>>);
+    let (tba_proof, set_tba_proof) = create_signal(None::<Result<String, String>>);
+
+    // Current fee suggestions, refreshed with the balances
+    let (fee_estimate, set_fee_estimate) = create_signal(None::<crate::fees::FeeEstimate>);
+
+    // HD Account Balances (by signer address)
+    let (account_bals, set_account_bals) = create_signal(std::collections::HashMap::<String, Result<Amount, String>>::new());
 
     
     // Refresh Trigger
@@ -35,6 +355,30 @@ pub fn WalletView() -> impl IntoView {
     
     // UI State
     let (show_clear_confirm, set_show_clear_confirm) = create_signal(false);
+    // Lets Reset Signer remove a wallet that may still hold funds
+    let (backup_confirmed, set_backup_confirmed) = create_signal(false);
+
+    // Watch-only Form State
+    let (show_watch_form, set_show_watch_form) = create_signal(false);
+    let (watch_address, set_watch_address) = create_signal("".to_string());
+    let (watch_tba, set_watch_tba) = create_signal("".to_string());
+
+    // V3 Keystore Import/Export State
+    let (pending_v3, set_pending_v3) = create_signal(None::<crate::crypto::V3Keystore>);
+    let (show_v3_export, set_show_v3_export) = create_signal(false);
+
+    // Shamir Split Backup State
+    let (show_split, set_show_split) = create_signal(false);
+    let (split_threshold, set_split_threshold) = create_signal(2u8);
+    let (split_total, set_split_total) = create_signal(3u8);
+    let (split_shares, set_split_shares) = create_signal(Vec::<Share>::new());
+    let (show_share_recovery, set_show_share_recovery) = create_signal(false);
+    let (recovery_shares, set_recovery_shares) = create_signal(Vec::<Share>::new());
+    let (share_input, set_share_input) = create_signal("".to_string());
+
+    // Vault UI State
+    let (new_wallet_name, set_new_wallet_name) = create_signal("".to_string());
+    let (confirm_delete, set_confirm_delete) = create_signal(None::<usize>);
 
     // Sponsor UI State
     let (show_sponsor_modal, set_show_sponsor_modal) = create_signal(false);
@@ -54,121 +398,664 @@ pub fn WalletView() -> impl IntoView {
     let (sa_recipient, set_sa_recipient) = create_signal("".to_string());
     let (sa_amount, set_sa_amount) = create_signal("".to_string());
 
-    // Load from local storage on init
+    // Vault storage (LocalStorage unless the browser blocks it)
+    let store = store_value(Backend::detect("diamond_wallet"));
+
+    let persist_vault = move |v: Vault| {
+        spawn_local(async move {
+            if let Err(e) = v.save(&store.get_value()).await {
+                set_status.set(format!("Storage Error: {}", e));
+            }
+        });
+    };
+
+    // Persist a keystore into the active vault slot (or a new one while onboarding)
+    let save_keystore = move |k: Keystore| {
+        let mut v = vault.get_untracked();
+        v.upsert_active(&new_wallet_name.get_untracked(), k.clone());
+        persist_vault(v.clone());
+        // Once a wallet lives in the vault the single-key slot is obsolete
+        spawn_local(async move {
+            let _ = store.get_value().delete(LEGACY_KEYSTORE_KEY).await;
+        });
+        set_new_wallet_name.set("".to_string());
+        set_vault.set(v);
+        set_keystore.set(k);
+    };
+
+    // Load from storage on init
     create_effect(move |_| {
-        if let Ok(k) = LocalStorage::get::<Keystore>("diamond_wallet_keystore") {
-            set_keystore.set(k);
+        spawn_local(async move {
+            let store = store.get_value();
+            let loaded = Settings::load(&store).await;
+            if loaded.persist_rpc_cache {
+                crate::cache::restore(&store).await;
+            }
+            set_settings.set(loaded);
+            let v = match Vault::load(&store).await {
+                Ok(v) => v,
+                Err(e) => { set_status.set(format!("Vault Error: {}", e)); return; }
+            };
+            if let Some(k) = v.active_keystore() {
+                set_keystore.set(k);
+                set_vault.set(v);
+                return;
+            }
+            set_vault.set(v);
+
+            // Migrate a pre-vault keystore. Plaintext keys stay in their slot until encrypted.
+            if let Ok(Some(raw)) = store.get_raw(LEGACY_KEYSTORE_KEY).await {
+                match parse_keystore(&raw) {
+                    Ok((k, None)) => save_keystore(k),
+                    Ok((k, legacy_key)) => {
+                        set_status.set("Unencrypted key found. Set a passphrase.".to_string());
+                        set_session.set(legacy_key.and_then(|pk| Session::from_hex(&pk).ok()));
+                        set_keystore.set(k);
+                    },
+                    Err(e) => set_status.set(format!("Keystore Error: {}", e)),
+                }
+            }
+        });
+    });
+
+    // Returns the session, or reports why signing is unavailable
+    let require_unlocked = move || -> Option<Session> {
+        if keystore.get_untracked().is_watch_only() {
+            set_status.set("Watch-only wallet: signing is disabled".to_string());
+            return None;
+        }
+        let session = session.get_untracked();
+        if session.is_none() {
+            set_status.set("Wallet Locked. Enter passphrase.".to_string());
+        }
+        session
+    };
+
+    let watch_only = move || keystore.get().is_watch_only();
+
+    // Drop the session; its key material zeroizes once no action still holds it
+    let lock_wallet = move |reason: &str| {
+        if session.get_untracked().is_none() { return; }
+        set_session.set(None);
+        set_show_wallet_menu.set(false);
+        set_show_device_send.set(false);
+        set_show_sa_send.set(false);
+        set_status.set(reason.to_string());
+    };
+
+    // Auto-lock: any pointer or key input counts as activity
+    let touch = move || last_activity.set_value(js_sys::Date::now());
+    let pointer_listener = window_event_listener(ev::pointerdown, move |_| touch());
+    let key_listener = window_event_listener(ev::keydown, move |_| touch());
+    let idle_timer = set_interval_with_handle(move || {
+        let minutes = settings.get_untracked().auto_lock_minutes;
+        if minutes == 0 { return; }
+        let idle_ms = js_sys::Date::now() - last_activity.get_value();
+        if idle_ms >= minutes as f64 * 60_000.0 {
+            lock_wallet("Locked after inactivity");
         }
+    }, std::time::Duration::from_secs(5)).ok();
+    on_cleanup(move || {
+        pointer_listener.remove();
+        key_listener.remove();
+        if let Some(t) = idle_timer { t.clear(); }
     });
 
+    let persist_settings = move |new_settings: Settings| {
+        set_settings.set(new_settings.clone());
+        spawn_local(async move {
+            if let Err(e) = new_settings.save(&store.get_value()).await {
+                set_status.set(format!("Storage Error: {}", e));
+            }
+        });
+    };
+
+    let set_auto_lock = move |minutes: u32| {
+        let mut new_settings = settings.get_untracked();
+        new_settings.auto_lock_minutes = minutes;
+        persist_settings(new_settings);
+        touch();
+    };
+
+    let switch_network = move |net: Network| {
+        if net == network() { return; }
+        let mut new_settings = settings.get_untracked();
+        new_settings.network = net;
+        persist_settings(new_settings);
+        set_signer_balance.set(None);
+        set_tba_balance.set(None);
+        set_account_bals.set(std::collections::HashMap::new());
+        set_status.set(format!("Network: {}", net.name()));
+    };
+
+    let set_rpc_quorum = move |quorum: u8| {
+        let mut new_settings = settings.get_untracked();
+        new_settings.rpc_quorum = quorum;
+        persist_settings(new_settings);
+    };
+
+    let set_verify_balances = move |verify: bool| {
+        let mut new_settings = settings.get_untracked();
+        new_settings.verify_balances = verify;
+        persist_settings(new_settings);
+        set_refresh_trigger.update(|v| *v += 1);
+    };
+
+    // Toggles legacy transactions for the current network only
+    let set_legacy_transactions = move |legacy: bool| {
+        let mut new_settings = settings.get_untracked();
+        let network = new_settings.network;
+        new_settings.legacy_networks.retain(|n| *n != network);
+        if legacy {
+            new_settings.legacy_networks.push(network);
+        }
+        persist_settings(new_settings);
+    };
+
+    let set_persist_cache = move |persist: bool| {
+        let mut new_settings = settings.get_untracked();
+        new_settings.persist_rpc_cache = persist;
+        persist_settings(new_settings);
+        if !persist {
+            spawn_local(async move { let _ = store.get_value().delete(crate::cache::CACHE_KEY).await; });
+        }
+    };
+
+    let clear_rpc_cache = move |_| {
+        crate::cache::clear();
+        spawn_local(async move { let _ = store.get_value().delete(crate::cache::CACHE_KEY).await; });
+        set_status.set("RPC cache cleared".to_string());
+    };
+
+    let set_fee_speed = move |speed: crate::fees::Speed| {
+        let mut new_settings = settings.get_untracked();
+        new_settings.fee_speed = speed;
+        persist_settings(new_settings);
+    };
+
+    // The provider reads the quorum on every critical batch
+    create_effect(move |_| crate::provider::set_read_quorum(settings.get().rpc_quorum as usize));
+    // Transaction builders read the speed when pricing
+    create_effect(move |_| crate::fees::set_speed(settings.get().fee_speed));
+    // ...and the envelope type of each network when signing
+    create_effect(move |_| crate::transactions::set_legacy_networks(settings.get().legacy_networks));
+
     // Refresh balances when keystore changes OR trigger updates
     create_effect(move |_| {
         let k = keystore.get();
+        let net = settings.get().network;
         let _ = refresh_trigger.get(); // Dependency
         
         if !k.address.is_empty() {
-            // Fetch Signer Balances
+            // Signer, TBA and HD account balances in one round trip
             spawn_local(async move {
-                let b_sep = get_balance(&k.address, Network::BaseSepolia).await;
-                set_bal_sepolia.set(b_sep);
-                
+                let mut batch = Batch::new();
+                let signer = batch.balance(&k.address);
+                let tba = k.smart_account.as_deref().map(|tba| batch.balance(tba));
+                let accounts: Vec<_> = k.accounts.iter().map(|acc| (acc.address.clone(), batch.balance(&acc.address))).collect();
+                let fees = crate::fees::FeeRequest::queue(&mut batch);
 
-            });
-            
-            // Fetch TBA Balances
-            if let Some(tba) = k.smart_account {
-                spawn_local(async move {
-                     let b_sep = get_balance(&tba, Network::BaseSepolia).await;
-                     set_tba_bal_sepolia.set(b_sep);
+                let mut resp = match batch.send(net).await {
+                    Ok(r) => r,
+                    Err(e) => {
+                        set_signer_balance.set(Some(Err(e.label())));
+                        set_tba_balance.set(Some(Err(e.label())));
+                        return;
+                    }
+                };
+                set_signer_balance.set(Some(resp.get(signer).map_err(|e| e.label())));
+                if let Some(tba) = tba {
+                    set_tba_balance.set(Some(resp.get(tba).map_err(|e| e.label())));
+                }
+                let bals: Vec<_> = accounts.into_iter().map(|(addr, p)| (addr, resp.get(p).map_err(|e| e.label()))).collect();
+                set_account_bals.update(|m| m.extend(bals));
+                set_fee_estimate.set(fees.redeem(&mut resp).ok());
 
+                // Verified mode: replace the numbers with ones proven against a trusted state root
+                if !settings.get_untracked().verify_balances { return; }
+                set_signer_proof.set(None);
+                set_tba_proof.set(None);
+                let header = match crate::proof::trusted_header(net).await {
+                    Ok(h) => h,
+                    Err(e) => {
+                        set_signer_proof.set(Some(Err(e.to_string())));
+                        set_tba_proof.set(Some(Err(e.to_string())));
+                        return;
+                    }
+                };
+                let verified = format!("✓ verified at block {}", header.number);
+                match crate::proof::get_account(net, &k.address, &header).await {
+                    Ok(account) => {
+                        set_signer_balance.set(Some(Ok(Amount::wei(account.balance))));
+                        set_signer_proof.set(Some(Ok(verified.clone())));
+                    },
+                    Err(e) => set_signer_proof.set(Some(Err(e.to_string()))),
+                }
+                let Some(tba) = k.smart_account else { return; };
+                // The proven code must be the ERC-6551 proxy of our identity NFT
+                let contracts = net.contracts();
+                let expected_code = contracts.tba_implementation.zip(contracts.identity_nft).map(|(implementation, nft)| {
+                    tba_creation_code(implementation, nft, net.chain_id(), IDENTITY_TOKEN_ID).1.split_off(TBA_CONSTRUCTOR.len() / 2)
                 });
-            }
+                let account = crate::proof::get_account(net, &tba, &header).await;
+                let code = crate::proof::get_code(net, &tba, &header).await;
+                set_tba_proof.set(Some(match (account, code) {
+                    (Ok(account), Ok(code)) => {
+                        set_tba_balance.set(Some(Ok(Amount::wei(account.balance))));
+                        if code.is_empty() {
+                            Ok(format!("{} · not deployed yet", verified))
+                        } else if Some(code) == expected_code {
+                            Ok(format!("{} · account of identity #{}", verified, IDENTITY_TOKEN_ID))
+                        } else {
+                            Err("the code at this address is not the identity NFT's account".to_string())
+                        }
+                    },
+                    (Err(e), _) | (_, Err(e)) => Err(e.to_string()),
+                }));
+            });
         }
     });
 
+    // Endpoints serving another chain than the selected network
+    let (chain_mismatch, set_chain_mismatch) = create_signal(None::<String>);
+    create_effect(move |_| {
+        let net = settings.get().network;
+        set_chain_mismatch.set(None);
+        spawn_local(async move {
+            if let Err(e @ crate::rpc::RpcError::WrongChain { .. }) = crate::provider::verify_chain(net).await {
+                set_chain_mismatch.set(Some(e.to_string()));
+            }
+        });
+    });
+
+    // Live updates: newHeads and incoming token transfers over WebSocket,
+    // falling back to polling eth_blockNumber while the socket is down
+    let (head, set_head) = create_signal(None::<u64>);
+    let (ws_live, set_ws_live) = create_signal(false);
+    let subscription = store_value(None::<futures::future::AbortHandle>);
+    create_effect(move |_| {
+        let net = settings.get().network;
+        let k = keystore.get();
+        if let Some(handle) = subscription.get_value() { handle.abort(); }
+        set_ws_live.set(false);
+        set_head.set(None);
+        if k.address.is_empty() { return; }
+
+        // Transfer(from, to, value) with `to` being one of our addresses
+        let watched: Vec<String> = std::iter::once(k.address.clone()).chain(k.smart_account.clone())
+            .map(|a| format!("0x{:0>64}", a.trim_start_matches("0x").to_lowercase()))
+            .collect();
+        let (handle, registration) = futures::future::AbortHandle::new_pair();
+        subscription.set_value(Some(handle));
+
+        let on_block = move |number: u64| {
+            if head.get_untracked() != Some(number) {
+                set_head.set(Some(number));
+                set_refresh_trigger.update(|v| *v += 1);
+                // Once per block is often enough to save what the cache learned
+                if settings.get_untracked().persist_rpc_cache {
+                    spawn_local(async move { let _ = crate::cache::persist(&store.get_value()).await; });
+                }
+            }
+        };
+        spawn_local(async move {
+            let task = async move {
+                loop {
+                    use crate::rpc::{Subscription, SubscriptionEvent};
+                    let subs = [
+                        Subscription::NewHeads,
+                        Subscription::Logs(serde_json::json!({ "topics": [crate::rpc::TRANSFER_TOPIC, null, watched] })),
+                    ];
+                    let _ = crate::rpc::subscribe(net, &subs, |event| match event {
+                        SubscriptionEvent::NewHead { number } => {
+                            set_ws_live.set(true);
+                            on_block(number);
+                        },
+                        SubscriptionEvent::Log(log) => {
+                            // Reorged-out logs are re-sent with `removed: true`
+                            if !log["removed"].as_bool().unwrap_or(false) {
+                                set_status.set(format!("Incoming token transfer ({})", log["address"].as_str().unwrap_or("unknown token")));
+                            }
+                            set_refresh_trigger.update(|v| *v += 1);
+                        },
+                    }).await;
+                    set_ws_live.set(false);
+
+                    // Poll for 30s, then try the socket again
+                    for _ in 0..6 {
+                        gloo_timers::future::TimeoutFuture::new(5_000).await;
+                        if let Ok(number) = crate::rpc::get_block_number(net).await {
+                            crate::rpc::notify_head(net, number);
+                            on_block(number);
+                        }
+                    }
+                }
+            };
+            let _ = futures::future::Abortable::new(task, registration).await;
+        });
+    });
+    on_cleanup(move || {
+        if let Some(handle) = subscription.get_value() { handle.abort(); }
+    });
+
+    // Encrypt a phrase, derive its first signer and persist it
+    let save_mnemonic_wallet = move |phrase: String, pass: String| {
+        spawn_local(async move {
+            set_status.set("Encrypting recovery phrase...".to_string());
+            // Let the status paint before scrypt blocks the thread
+            gloo_timers::future::TimeoutFuture::new(0).await;
+
+            use ethers_signers::Signer;
+
+            let path = crate::mnemonic::account_path(0);
+            let wallet = match crate::mnemonic::derive_wallet(&phrase, &path) {
+                Ok(w) => w,
+                Err(e) => { set_status.set(format!("Derive Error: {}", e)); return; }
+            };
+            let crypto = match crate::crypto::encrypt(phrase.as_bytes(), &pass) {
+                Ok(c) => c,
+                Err(e) => { set_status.set(format!("Encrypt Error: {}", e)); return; }
+            };
+
+            let address = format!("{:?}", wallet.address());
+            let new_ks = Keystore {
+                schema: KEYSTORE_SCHEMA,
+                address: address.clone(),
+                smart_account: None,
+                crypto: Some(crypto),
+                source: KeySource::Mnemonic { path: path.clone() },
+                accounts: vec![HdAccount { path, address, smart_account: None }],
+                active_account: 0,
+            };
+
+            save_keystore(new_ks);
+            set_session.set(Some(Session::new(&wallet, Some(phrase))));
+            set_passphrase.set("".to_string());
+            set_pending_mnemonic.set(None);
+            set_confirm_input.set("".to_string());
+            set_mnemonic_input.set("".to_string());
+            set_show_mnemonic_import.set(false);
+            set_status.set("Wallet Ready".to_string());
+        });
+    };
+
     let generate_wallet = move |_| {
-        set_status.set("Generating secure key...".to_string());
-        
-        // Generate real random key
-        let mut rng = SmallRng::from_entropy();
-        let mut pk_bytes = [0u8; 32];
-        rng.fill_bytes(&mut pk_bytes);
-        let pk_hex = hex::encode(pk_bytes);
-        
-        // Properly derive address using ethers
-        use ethers_signers::{LocalWallet, Signer};
-        
-        let wallet = LocalWallet::from_bytes(&pk_bytes).expect("Valid bytes");
-        let addr = wallet.address(); // This is the REAL address
-        let addr_hex = format!("{:?}", addr);
+        if passphrase.get_untracked().len() < 8 {
+            set_status.set("Passphrase must be at least 8 characters".to_string());
+            return;
+        }
 
-        let new_ks = Keystore {
-            private_key: format!("0x{}", pk_hex),
-            address: addr_hex,
-            smart_account: None,
+        match crate::mnemonic::generate(word_count.get_untracked()) {
+            Ok(phrase) => {
+                set_pending_mnemonic.set(Some(phrase));
+                set_confirm_indices.set(Vec::new());
+                set_status.set("Write down your recovery phrase".to_string());
+            },
+            Err(e) => set_status.set(format!("Mnemonic Error: {}", e)),
+        }
+    };
+
+    // Pick three word positions the user has to repeat back
+    let start_confirm = move |_| {
+        let count = word_count.get_untracked();
+        let mut picks = Vec::new();
+        while picks.len() < 3 {
+            let i = (OsRng.next_u32() as usize) % count;
+            if !picks.contains(&i) { picks.push(i); }
+        }
+        picks.sort();
+        set_confirm_indices.set(picks);
+        set_confirm_input.set("".to_string());
+    };
+
+    let confirm_mnemonic = move |_| {
+        let Some(phrase) = pending_mnemonic.get_untracked() else { return; };
+        let words: Vec<&str> = phrase.split_whitespace().collect();
+        let expected: Vec<&str> = confirm_indices.get_untracked().iter().map(|i| words[*i]).collect();
+        let input = confirm_input.get_untracked().to_lowercase();
+        let given: Vec<&str> = input.split_whitespace().collect();
+
+        if given != expected {
+            set_status.set("Words do not match. Check your backup.".to_string());
+            return;
+        }
+        save_mnemonic_wallet(phrase, passphrase.get_untracked());
+    };
+
+    let import_mnemonic = move |_| {
+        let pass = passphrase.get_untracked();
+        if pass.len() < 8 {
+            set_status.set("Passphrase must be at least 8 characters".to_string());
+            return;
+        }
+        match crate::mnemonic::normalize(&mnemonic_input.get_untracked()) {
+            Ok(phrase) => save_mnemonic_wallet(phrase, pass),
+            Err(e) => set_status.set(format!("Invalid Phrase: {}", e)),
+        }
+    };
+
+    // Decrypt the stored key with the passphrase
+    let unlock_wallet = move |_| {
+        let k = keystore.get_untracked();
+        let pass = passphrase.get_untracked();
+        let Some(crypto) = k.crypto.clone() else { return; };
+
+        spawn_local(async move {
+            set_status.set("Unlocking...".to_string());
+            gloo_timers::future::TimeoutFuture::new(0).await;
+
+            use ethers_signers::Signer;
+
+            let secret = match crate::crypto::decrypt(&crypto, &pass) {
+                Ok(b) => b,
+                Err(e) => { set_status.set(format!("Unlock Failed: {}", e)); return; }
+            };
+            let wallet = match wallet_from_secret(&k, &secret) {
+                Ok(w) => w,
+                Err(e) => { set_status.set(format!("Invalid Key: {}", e)); return; }
+            };
+            if !format!("{:?}", wallet.address()).eq_ignore_ascii_case(&k.address) {
+                set_status.set("Unlock Failed: key does not match address".to_string());
+                return;
+            }
+
+            let phrase = if k.active_path().is_some() { std::str::from_utf8(&secret).ok().map(str::to_string) } else { None };
+            set_session.set(Some(Session::new(&wallet, phrase)));
+            set_passphrase.set("".to_string());
+            set_status.set("Wallet Unlocked".to_string());
+        });
+    };
+
+    // Encrypt a legacy plaintext key in place
+    let encrypt_existing = move |_| {
+        let pass = passphrase.get_untracked();
+        if pass.len() < 8 {
+            set_status.set("Passphrase must be at least 8 characters".to_string());
+            return;
+        }
+        let Some(session) = session.get_untracked() else { return; };
+
+        spawn_local(async move {
+            set_status.set("Encrypting key...".to_string());
+            gloo_timers::future::TimeoutFuture::new(0).await;
+
+            let crypto = match crate::crypto::encrypt(session.key_bytes(), &pass) {
+                Ok(c) => c,
+                Err(e) => { set_status.set(format!("Encrypt Error: {}", e)); return; }
+            };
+
+            let mut new_ks = keystore.get_untracked();
+            new_ks.crypto = Some(crypto);
+            save_keystore(new_ks);
+            set_passphrase.set("".to_string());
+            set_status.set("Key Encrypted".to_string());
+        });
+    };
+
+    // Track an address (and optionally its TBA) without holding a key
+    let add_watch_only = move |_| {
+        use std::str::FromStr;
+        use ethers_core::types::Address;
+
+        let address = match Address::from_str(watch_address.get_untracked().trim()) {
+            Ok(a) => format!("{:?}", a),
+            Err(_) => { set_status.set("Invalid Watch Address".to_string()); return; }
         };
-        
-        let _ = LocalStorage::set("diamond_wallet_keystore", &new_ks);
-        set_keystore.set(new_ks);
-        set_status.set("New Wallet Generated".to_string());
+        let tba_input = watch_tba.get_untracked();
+        let smart_account = if tba_input.trim().is_empty() {
+            None
+        } else {
+            match Address::from_str(tba_input.trim()) {
+                Ok(a) => Some(format!("{:?}", a)),
+                Err(_) => { set_status.set("Invalid TBA Address".to_string()); return; }
+            }
+        };
+
+        save_keystore(Keystore {
+            schema: KEYSTORE_SCHEMA,
+            address,
+            smart_account,
+            crypto: None,
+            source: KeySource::WatchOnly,
+            accounts: Vec::new(),
+            active_account: 0,
+        });
+        set_watch_address.set("".to_string());
+        set_watch_tba.set("".to_string());
+        set_show_watch_form.set(false);
+        set_status.set("Watching Address".to_string());
+    };
+
+    // Derive the next BIP-44 account from the session phrase
+    let add_account = move |_| {
+        let Some(phrase) = session.get_untracked().and_then(|s| s.phrase().map(str::to_string)) else {
+            set_status.set("Unlock a recovery-phrase wallet to add accounts".to_string());
+            return;
+        };
+        use ethers_signers::Signer;
+
+        let mut new_ks = keystore.get_untracked();
+        let path = crate::mnemonic::account_path(new_ks.accounts.len() as u32);
+        match crate::mnemonic::derive_wallet(&phrase, &path) {
+            Ok(w) => {
+                let address = format!("{:?}", w.address());
+                new_ks.accounts.push(HdAccount { path, address: address.clone(), smart_account: None });
+                save_keystore(new_ks);
+                set_status.set(format!("Added Account {}", address));
+            },
+            Err(e) => set_status.set(format!("Derive Error: {}", e)),
+        }
+    };
+
+    // Switch the active signer, re-deriving its key if unlocked
+    let switch_account = move |index: usize| {
+        let mut new_ks = keystore.get_untracked();
+        if index == new_ks.active_account { return; }
+        new_ks.select_account(index);
+
+        let next = session.get_untracked().and_then(|s| {
+            let phrase = s.phrase()?;
+            new_ks.active_path()
+                .and_then(|path| crate::mnemonic::derive_wallet(phrase, &path).ok())
+                .map(|w| s.with_wallet(&w))
+        });
+
+        set_session.set(next);
+        set_signer_balance.set(None);
+        set_tba_balance.set(None);
+        set_status.set(format!("Switched to Account {}", index));
+        save_keystore(new_ks);
+    };
+
+    // Make another vault wallet active. Its key stays locked until unlocked.
+    let switch_wallet = move |index: usize| {
+        let mut v = vault.get_untracked();
+        if v.active == Some(index) { return; }
+        v.active = Some(index);
+        persist_vault(v.clone());
+
+        set_session.set(None);
+        set_signer_balance.set(None);
+        set_tba_balance.set(None);
+        set_keystore.set(v.active_keystore().unwrap_or_default());
+        set_vault.set(v);
+        set_status.set("Wallet Switched".to_string());
+    };
+
+    // Leave the current wallet in the vault and show onboarding for a new one
+    let add_wallet = move |_| {
+        let mut v = vault.get_untracked();
+        v.active = None;
+        set_session.set(None);
+        set_keystore.set(Keystore::default());
+        set_vault.set(v);
+        set_show_wallet_menu.set(false);
+    };
+
+    let delete_wallet = move |index: usize| {
+        let mut v = vault.get_untracked();
+        let was_active = v.active == Some(index);
+        v.remove(index);
+        persist_vault(v.clone());
+
+        if was_active {
+            set_session.set(None);
+            set_signer_balance.set(None);
+            set_tba_balance.set(None);
+            set_keystore.set(v.active_keystore().unwrap_or_default());
+        }
+        set_vault.set(v);
+        set_confirm_delete.set(None);
+        set_status.set("Wallet Removed".to_string());
     };
 
     // Sweep Funds Logic
     let sweep_funds = move |_| {
+        let net = network();
         let k = keystore.get();
-        if k.private_key.is_empty() { return; }
+        let Some(session) = require_unlocked() else { return; };
+        let Some(deployer) = net.contracts().deployer else {
+            set_status.set(format!("No sweep target on {}", net.name()));
+            return;
+        };
         
         spawn_local(async move {
             let feedback = crate::transactions::TxFeedback::new(set_status);
             feedback.set("Preparing to sweep funds...");
             
             // Importing ethers types here to construct tx
-            use ethers_core::types::{TransactionRequest, U256};
-            use ethers_signers::{LocalWallet, Signer};
+            use ethers_core::types::{Eip1559TransactionRequest, U256};
+            use ethers_signers::Signer;
             
-            let pk = k.private_key.trim_start_matches("0x");
-            let wallet: LocalWallet = match pk.parse() {
-                Ok(w) => w,
-                Err(e) => {
-                    feedback.set(&format!("Error parsing key: {}", e));
-                    return;
-                }
-            };
-            let wallet = wallet.with_chain_id(84532u64);
+            let wallet = session.wallet().with_chain_id(net.chain_id());
             
-             // Raw balance check via manual RPC call
-            let params = serde_json::json!([k.address, "latest"]);
-            let bal_res = crate::rpc::eth_call(Network::BaseSepolia, "eth_getBalance", params).await;
-            let balance = match bal_res {
-                Ok(v) => {
-                   let hex = v["result"].as_str().unwrap_or("0");
-                   u128::from_str_radix(hex.trim_start_matches("0x"), 16).unwrap_or(0)
-                },
-                Err(_) => 0
+            let balance = match crate::rpc::get_balance(net, &k.address).await {
+                Ok(b) => b,
+                Err(e) => { feedback.set(&format!("Balance Error: {}", e)); return; }
+            };
+            let fees = match crate::fees::suggest(net).await {
+                Ok(fees) => fees.sweep(),
+                Err(e) => { feedback.set(&format!("Gas Price Error: {}", e)); return; }
             };
 
-            let gas_price = crate::rpc::get_gas_price(Network::BaseSepolia).await.unwrap_or(0);
-            let effective_gas_price = gas_price + (gas_price / 10);
             let gas_limit = 21000u64;
-            let cost = effective_gas_price * (gas_limit as u128);
-            let safety_buffer = 10_000u128;
-            let total_deduct = cost + safety_buffer;
+            let safety_buffer = Amount::wei(10_000u64);
+            let total_deduct = fees.max_fee_per_gas.checked_mul(gas_limit).and_then(|cost| cost.checked_add(safety_buffer));
 
-            if balance <= total_deduct {
+            let Some(send_amount) = total_deduct.and_then(|d| balance.checked_sub(d)).filter(|a| !a.is_zero()) else {
                 feedback.set("Insufficient funds to cover gas.");
                 return;
-            }
-            
-            let send_amount = balance - total_deduct;
-            let deployer_addr: ethers_core::types::Address = "0x769c18faa2e2e833a262c2ff9f6e1a9e99e52c58".parse().unwrap();
+            };
+            let deployer_addr: ethers_core::types::Address = deployer.parse().unwrap();
             
-            let tx = TransactionRequest::new()
+            let tx = Eip1559TransactionRequest::new()
                 .to(deployer_addr)
-                .value(U256::from(send_amount))
+                .value(send_amount.raw())
                 .gas(U256::from(gas_limit))
-                .gas_price(U256::from(effective_gas_price));
+                .max_fee_per_gas(fees.max_fee_per_gas.raw())
+                .max_priority_fee_per_gas(fees.max_priority_fee_per_gas.raw());
                 
-            let lat = crate::transactions::send_with_feedback(&wallet, tx, feedback, "Swept!").await;
+            let lat = crate::transactions::send_with_feedback(&wallet, net, tx, TxKind::Sweep, feedback, "Swept!").await;
             set_last_latency.set(lat);
             set_refresh_trigger.update(|v| *v += 1);
         });
@@ -176,81 +1063,63 @@ pub fn WalletView() -> impl IntoView {
 
     // Mint Identity Logic
     let mint_identity = move |_| {
-        let k = keystore.get();
-        if k.private_key.is_empty() { return; }
+        let net = network();
+        let Some(session) = require_unlocked() else { return; };
+        let Some(nft) = net.contracts().identity_nft else {
+            set_status.set(format!("Identity NFT is not deployed on {}", net.name()));
+            return;
+        };
         
         spawn_local(async move {
             let feedback = crate::transactions::TxFeedback::new(set_status);
             feedback.set("Initializing NFT Mint...");
             
-            let nft_addr: ethers_core::types::Address = "0x66994e547cb9014191f50c7c7ee8cf5e80d3b89e".parse().unwrap();
+            let nft_addr: ethers_core::types::Address = nft.parse().unwrap();
             
-            use ethers_core::types::{TransactionRequest, U256, Bytes};
-            use ethers_signers::{LocalWallet, Signer};
+            use ethers_core::types::{Eip1559TransactionRequest, U256, Bytes};
+            use ethers_signers::Signer;
 
-            let pk = k.private_key.trim_start_matches("0x");
-            let wallet: LocalWallet = pk.parse().unwrap();
-            let wallet = wallet.with_chain_id(84532u64);
-            
-            let gas_price = crate::rpc::get_gas_price(Network::BaseSepolia).await.unwrap_or(0);
+            let wallet = session.wallet().with_chain_id(net.chain_id());
             
             // Selector for mint() is 0x1249c58b
             let data = Bytes::from(hex::decode("1249c58b").unwrap());
             
-            let tx = TransactionRequest::new()
+            let tx = Eip1559TransactionRequest::new()
                 .to(nft_addr)
                 .value(U256::zero())
                 .gas(U256::from(200_000u64)) 
-                .gas_price(U256::from(gas_price))
                 .data(data);
                 
-            let lat = crate::transactions::send_with_feedback(&wallet, tx, feedback, "Mint Confirmed! Compute TBA now.").await;
+            let lat = crate::transactions::send_with_feedback(&wallet, net, tx, TxKind::Mint, feedback, "Mint Confirmed! Compute TBA now.").await;
             set_last_latency.set(lat);
             set_refresh_trigger.update(|v| *v += 1);
         });
     };
 
     let compute_tba = move |_| {
+        let net = network();
          let k = keystore.get();
          if k.address.is_empty() { return; }
+         let contracts = net.contracts();
+         let (Some(registry_addr), Some(implementation_addr), Some(nft_addr)) =
+             (contracts.erc6551_registry, contracts.tba_implementation, contracts.identity_nft) else {
+             set_status.set(format!("Identity contracts are not deployed on {}", net.name()));
+             return;
+         };
          
          spawn_local(async move {
             leptos::logging::log!("Starting TBA Compute...");
             set_status.set("Locating TBA Address...".to_string());
             
-            // PLACEHOLDERS
-            let registry_addr = "0x000000006551c19487814612e58FE06813775758";
-            let implementation_addr = "0xfb28ae9ffc69dd62718a780cb657a59c0b4e7aae"; 
-            let nft_addr = "0x66994e547cb9014191f50c7c7ee8cf5e80d3b89e"; 
-            let chain_id = 84532u64;
-            let token_id = 1u64; 
-
-            use ethers_core::abi::{encode, Token};
+            let chain_id = net.chain_id();
+            let token_id = IDENTITY_TOKEN_ID;
+
+            use ethers_core::abi::Token;
             use ethers_core::utils::keccak256;
             use ethers_core::types::U256;
             
-            // 1. Calculate Salt
-            let salt_bytes = encode(&[
-                Token::Uint(U256::from(chain_id)),
-                Token::Address(nft_addr.parse().unwrap()),
-                Token::Uint(U256::from(token_id))
-            ]);
-            let salt = keccak256(salt_bytes);
-            
-            // 2. Creation Code (ERC-6551 v3 Standard)
-            let mut creation_code = Vec::new();
-            creation_code.extend(hex::decode("3d60ad80600a3d3981f3363d3d373d3d3d363d73").unwrap());
-            creation_code.extend(hex::decode(implementation_addr.trim_start_matches("0x")).unwrap());
-            creation_code.extend(hex::decode("5af43d82803e903d91602b57fd5bf3").unwrap()); 
-            
-            // Footer Data
-            let footer_data = encode(&[
-                Token::FixedBytes(salt.to_vec()),
-                Token::Uint(U256::from(chain_id)),
-                Token::Address(nft_addr.parse().unwrap()),
-                Token::Uint(U256::from(token_id))
-            ]);
-            creation_code.extend(footer_data);
+            // 1-2. Salt and Creation Code (ERC-6551 v3 Standard)
+            let (salt, creation_code) = tba_creation_code(implementation_addr, nft_addr, chain_id, token_id);
             
             // 3. Init Code Hash
             let init_code_hash = keccak256(&creation_code);
@@ -265,18 +1134,40 @@ pub fn WalletView() -> impl IntoView {
             let raw_addr_hash = keccak256(&input);
             let addr_bytes = &raw_addr_hash[12..32];
             let addr_hex = format!("0x{}", hex::encode(addr_bytes));
+
+            // 5. Cross-check with the registry at the block on screen
+            let account_fn = crate::abi::ContractFn::parse(
+                "function account(address implementation, bytes32 salt, uint256 chainId, address tokenContract, uint256 tokenId) view returns (address)"
+            ).unwrap();
+            let args = [
+                Token::Address(implementation_addr.parse().unwrap()),
+                Token::FixedBytes(salt.to_vec()),
+                Token::Uint(U256::from(chain_id)),
+                Token::Address(nft_addr.parse().unwrap()),
+                Token::Uint(U256::from(token_id)),
+            ];
+            let block = head.get_untracked().map(crate::rpc::BlockTag::Number).unwrap_or(crate::rpc::BlockTag::Latest);
+            let registry_says = crate::rpc::call_contract(net, registry_addr, &account_fn, &args, block).await
+                .map(|out| out.into_iter().next().and_then(Token::into_address));
             
             let mut new_ks = k.clone();
-            new_ks.smart_account = Some(addr_hex.clone());
-            let _ = LocalStorage::set("diamond_wallet_keystore", &new_ks);
-            set_keystore.set(new_ks);
-            set_status.set(format!("TBA Computed: {}", addr_hex));
+            new_ks.set_smart_account(Some(addr_hex.clone()));
+            save_keystore(new_ks);
+            match registry_says {
+                Ok(Some(addr)) if format!("{:?}", addr) != addr_hex => {
+                    set_status.set(format!("TBA Computed: {} (registry reports {:?})", addr_hex, addr));
+                },
+                Err(e) => set_status.set(format!("TBA Computed: {} (registry check failed: {})", addr_hex, e)),
+                _ => set_status.set(format!("TBA Computed: {}", addr_hex)),
+            }
             set_refresh_trigger.update(|v| *v += 1);
          });
     };
 
     // Send ETH (Device)
     let send_eth_device = move |_| {
+        let net = network();
+        let Some(session) = require_unlocked() else { return; };
         spawn_local(async move {
             let to = device_recipient.get_untracked();
             let amt_str = device_amount.get_untracked();
@@ -287,32 +1178,28 @@ pub fn WalletView() -> impl IntoView {
             }
             
             use std::str::FromStr;
-            use ethers_core::utils::parse_ether;
-            use ethers_core::types::{Address, TransactionRequest};
-            use ethers_signers::{LocalWallet, Signer};
+            use ethers_core::types::{Address, Eip1559TransactionRequest};
+            use ethers_signers::Signer;
 
             let to_addr = match Address::from_str(&to) {
                 Ok(a) => a,
                 Err(_) => { set_status.set("Invalid Recipient Address".to_string()); return; }
             };
             
-            let val = match parse_ether(&amt_str) {
-                Ok(v) => v,
-                Err(_) => { set_status.set("Invalid Amount".to_string()); return; }
+            let val = match Amount::parse_ether(&amt_str) {
+                Ok(v) => v.raw(),
+                Err(e) => { set_status.set(format!("Invalid Amount: {}", e)); return; }
             };
 
             let feedback = crate::transactions::TxFeedback::new(set_status);
             feedback.set("Sending ETH...");
             
-            let k = keystore.get_untracked();
-            let pk = k.private_key.trim_start_matches("0x");
-            let wallet: LocalWallet = pk.parse().unwrap();
-            let wallet = wallet.with_chain_id(84532u64);
+            let wallet = session.wallet().with_chain_id(net.chain_id());
             
             // Construct TX (no provider needed here, send_with_feedback handles it via raw RPC)
-            let tx = TransactionRequest::new().to(to_addr).value(val);
+            let tx = Eip1559TransactionRequest::new().to(to_addr).value(val);
             
-            let lat = crate::transactions::send_with_feedback(&wallet, tx, feedback, "ETH Sent!").await;
+            let lat = crate::transactions::send_with_feedback(&wallet, net, tx, TxKind::Send, feedback, "ETH Sent!").await;
             
             if lat.is_some() {
                  set_show_device_send.set(false);
@@ -325,6 +1212,8 @@ pub fn WalletView() -> impl IntoView {
 
     // Send ETH (Smart Account)
     let send_eth_sa = move |_| {
+        let net = network();
+        let Some(session) = require_unlocked() else { return; };
         spawn_local(async move {
             let to = sa_recipient.get_untracked();
             let amt_str = sa_amount.get_untracked();
@@ -335,7 +1224,6 @@ pub fn WalletView() -> impl IntoView {
             }
 
             use std::str::FromStr;
-            use ethers_core::utils::parse_ether;
             use ethers_core::types::Address;
 
              let to_addr = match Address::from_str(&to) {
@@ -343,9 +1231,9 @@ pub fn WalletView() -> impl IntoView {
                 Err(_) => { set_status.set("Invalid Recipient Address".to_string()); return; }
             };
             
-            let val = match parse_ether(&amt_str) {
-                Ok(v) => v,
-                Err(_) => { set_status.set("Invalid Amount".to_string()); return; }
+            let val = match Amount::parse_ether(&amt_str) {
+                Ok(v) => v.raw(),
+                Err(e) => { set_status.set(format!("Invalid Amount: {}", e)); return; }
             };
 
             let feedback = crate::transactions::TxFeedback::new(set_status);
@@ -372,17 +1260,17 @@ pub fn WalletView() -> impl IntoView {
                    calldata.extend(args);
 
                    // Create wallet
-                   use ethers_signers::{LocalWallet, Signer};
-                   use ethers_core::types::TransactionRequest;
+                   use ethers_signers::Signer;
+                   use ethers_core::types::Eip1559TransactionRequest;
                    
-                   let pk = k.private_key.trim_start_matches("0x");
-                   let wallet: LocalWallet = pk.parse().unwrap();
-                   let wallet = wallet.with_chain_id(84532u64);
+                   let wallet = session.wallet().with_chain_id(net.chain_id());
 
                    // Send transaction to TBA from signer
                    let _ = crate::transactions::send_with_feedback(
                        &wallet,
-                       TransactionRequest::new().to(tba).data(calldata).value(0), // 0 value to TBA, TBA sends value to dest
+                       net,
+                       Eip1559TransactionRequest::new().to(tba).data(calldata).value(0), // 0 value to TBA, TBA sends value to dest
+                       TxKind::TbaSend,
                        feedback,
                        "Sent ETH via TBA!"
                    ).await;
@@ -399,90 +1287,35 @@ pub fn WalletView() -> impl IntoView {
     };
 
     let clear_wallet = move |_| {
+        let net = network();
         let k = keystore.get();
-        if k.private_key.is_empty() { return; }
-        
+        if k.address.is_empty() { return; }
+        let session = session.get_untracked();
+        let backed_up = backup_confirmed.get_untracked();
+
         spawn_local(async move {
-             let feedback = crate::transactions::TxFeedback::new(set_status);
-             use ethers_core::types::{TransactionRequest, U256};
-             use ethers_signers::{LocalWallet, Signer};
-             use ethers_core::abi::{encode, Token};
-
-             let pk = k.private_key.trim_start_matches("0x");
-             // If key invalid, just force clear
-             if let Ok(w) = pk.parse::<LocalWallet>() {
-                let wallet = w.with_chain_id(84532u64);
-                let deployer_addr: ethers_core::types::Address = "0x769c18faa2e2e833a262c2ff9f6e1a9e99e52c58".parse().unwrap();
-                
-                // 1. Drain TBA if it exists
-                if let Some(tba) = k.smart_account {
-                    feedback.set("Checking TBA balance...");
-                    let params = serde_json::json!([tba, "latest"]);
-                    if let Ok(v) = crate::rpc::eth_call(Network::BaseSepolia, "eth_getBalance", params).await {
-                         let hex = v["result"].as_str().unwrap_or("0");
-                         let tba_bal = u128::from_str_radix(hex.trim_start_matches("0x"), 16).unwrap_or(0);
-                         
-                         if tba_bal > 0 {
-                             feedback.set("Draining TBA...");
-                             let execute_selector = hex::decode("24857bd4").unwrap();
-                             let inner_data = encode(&[
-                                Token::Address(deployer_addr),
-                                Token::Uint(U256::from(tba_bal)),
-                                Token::Bytes(vec![]), 
-                                Token::Uint(U256::from(0u8)) 
-                            ]);
-                            let mut tx_data = execute_selector;
-                            tx_data.extend(inner_data);
-                            
-                            let gas_price = crate::rpc::get_gas_price(Network::BaseSepolia).await.unwrap_or(0);
-                            let effective_gas_price = gas_price + (gas_price / 10);
-                            
-                             let tx = TransactionRequest::new()
-                                .to(tba.parse::<ethers_core::types::Address>().unwrap())
-                                .value(0) 
-                                .data(tx_data)
-                                .gas(U256::from(200000u64)) // TBA overhead
-                                .gas_price(U256::from(effective_gas_price));
-                                
-                             let _ = crate::transactions::send_with_feedback(&wallet, tx, feedback.clone(), "TBA Drained!").await;
-                         }
-                    }
-                }
+            let feedback = crate::transactions::TxFeedback::new(set_status);
 
-                // 2. Drain Signer
-                feedback.set("Checking Signer balance...");
-                let params = serde_json::json!([k.address, "latest"]);
-                if let Ok(bal_res) = crate::rpc::eth_call(Network::BaseSepolia, "eth_getBalance", params).await {
-                     let hex = bal_res["result"].as_str().unwrap_or("0");
-                     let balance = u128::from_str_radix(hex.trim_start_matches("0x"), 16).unwrap_or(0);
-                     
-                     let gas_price = crate::rpc::get_gas_price(Network::BaseSepolia).await.unwrap_or(0);
-                     let effective_gas_price = gas_price + (gas_price / 10);
-                     let gas_limit = 21000u64;
-                     let cost = effective_gas_price * (gas_limit as u128);
-                     let safety_buffer = 1000u128; // Tiny buffer
-                     
-                     if balance > (cost + safety_buffer) {
-                          feedback.set("Draining Signer...");
-                          let send_amount = balance - (cost + safety_buffer);
-                          
-                          let tx = TransactionRequest::new()
-                             .to(deployer_addr)
-                             .value(U256::from(send_amount))
-                             .gas(U256::from(gas_limit))
-                             .gas_price(U256::from(effective_gas_price));
-                             
-                         let _ = crate::transactions::send_with_feedback(&wallet, tx, feedback, "Signer Drained!").await;
-                     }
-                }
-             }
+            // 1. Drain every account; a watch-only wallet holds no key to lose
+            let blockers = if k.is_watch_only() { Vec::new() } else { drain_all(net, &k, session, feedback).await };
+            if !blockers.is_empty() && !backed_up {
+                set_status.set(format!("Wallet kept: {}. Confirm you have a backup to remove it anyway.", blockers.join("; ")));
+                return;
+            }
 
-            // 3. Perform Clear
-            let _ = LocalStorage::delete("diamond_wallet_keystore");
-            set_keystore.set(Keystore::default());
-            set_bal_sepolia.set("...".to_string());
+            // 2. Remove this wallet from the vault (others are kept)
+            let mut v = vault.get_untracked();
+            if let Some(i) = v.active {
+                v.remove(i);
+            }
+            persist_vault(v.clone());
+            set_keystore.set(v.active_keystore().unwrap_or_default());
+            set_vault.set(v);
+            set_session.set(None);
+            set_signer_balance.set(None);
 
             set_show_clear_confirm.set(false);
+            set_backup_confirmed.set(false);
             set_status.set("Wallet Cleared".to_string());
         });
     };
@@ -496,6 +1329,11 @@ pub fn WalletView() -> impl IntoView {
     }
 
     let request_sponsor = move |_| {
+        let net = network();
+        if !net.is_testnet() {
+            set_status.set("Sponsoring is only available on testnets".to_string());
+            return;
+        }
         let pin = pin_input.get();
         if pin != "1337" {
              set_status.set("Incorrect PIN.".to_string());
@@ -522,30 +1360,33 @@ pub fn WalletView() -> impl IntoView {
             // Faucet Key (Deployer - DEMO ONLY) - Loaded from Env
             let faucet_pk = option_env!("FAUCET_KEY").unwrap_or("");
             
-            use ethers_core::types::{TransactionRequest, U256};
+            use ethers_core::types::{Eip1559TransactionRequest, U256};
             use ethers_signers::{LocalWallet, Signer};
             
             let wallet: LocalWallet = faucet_pk.parse().unwrap();
-            let wallet = wallet.with_chain_id(84532u64);
+            let wallet = wallet.with_chain_id(net.chain_id());
             
-            let gas_price = crate::rpc::get_gas_price(Network::BaseSepolia).await.unwrap_or(0);
             let amount = U256::from(5000000000000000u64); // 0.005 ETH
             let gas_limit = 21000u64;
             let target_addr: ethers_core::types::Address = k.address.parse().unwrap();
             
-            let tx = TransactionRequest::new()
+            let tx = Eip1559TransactionRequest::new()
                 .to(target_addr)
                 .value(amount)
-                .gas(U256::from(gas_limit))
-                .gas_price(U256::from(gas_price));
+                .gas(U256::from(gas_limit));
                 
-            let lat = crate::transactions::send_with_feedback(&wallet, tx, feedback, "Sponsored!").await;
+            let lat = crate::transactions::send_with_feedback(&wallet, net, tx, TxKind::Sponsor, feedback, "Sponsored!").await;
             set_last_latency.set(lat);
             set_refresh_trigger.update(|v| *v += 1);
         });
     };
 
     let request_tba_sponsor = move |_| {
+        let net = network();
+        if !net.is_testnet() {
+            set_status.set("Sponsoring is only available on testnets".to_string());
+            return;
+        }
         let k = keystore.get();
         if let Some(tba) = k.smart_account {
              spawn_local(async move {
@@ -553,24 +1394,22 @@ pub fn WalletView() -> impl IntoView {
                 feedback.set("Sponsoring TBA...");
                 
                 let faucet_pk = option_env!("FAUCET_KEY").unwrap_or("");
-                use ethers_core::types::{TransactionRequest, U256};
+                use ethers_core::types::{Eip1559TransactionRequest, U256};
                 use ethers_signers::{LocalWallet, Signer};
                 
                 let wallet: LocalWallet = faucet_pk.parse().unwrap();
-                let wallet = wallet.with_chain_id(84532u64);
+                let wallet = wallet.with_chain_id(net.chain_id());
                 
-                let gas_price = crate::rpc::get_gas_price(Network::BaseSepolia).await.unwrap_or(0);
                 let amount = U256::from(5000000000000000u64); // 0.005 ETH
                 let gas_limit = 21000u64;
                 let target_addr: ethers_core::types::Address = tba.parse().unwrap();
                 
-                let tx = TransactionRequest::new()
+                let tx = Eip1559TransactionRequest::new()
                     .to(target_addr)
                     .value(amount)
-                    .gas(U256::from(gas_limit))
-                    .gas_price(U256::from(gas_price));
+                    .gas(U256::from(gas_limit));
                     
-                let lat = crate::transactions::send_with_feedback(&wallet, tx, feedback, "TBA Sponsored!").await;
+                let lat = crate::transactions::send_with_feedback(&wallet, net, tx, TxKind::Sponsor, feedback, "TBA Sponsored!").await;
                 set_last_latency.set(lat);
                 set_refresh_trigger.update(|v| *v += 1);
              });
@@ -578,30 +1417,30 @@ pub fn WalletView() -> impl IntoView {
     };
     
     let sweep_tba_funds = move |_| {
+        let net = network();
         let k = keystore.get();
-        if k.private_key.is_empty() { return; }
+        let Some(session) = require_unlocked() else { return; };
+        let Some(deployer) = net.contracts().deployer else {
+            set_status.set(format!("No sweep target on {}", net.name()));
+            return;
+        };
         if let Some(tba) = k.smart_account {
              spawn_local(async move {
                 let feedback = crate::transactions::TxFeedback::new(set_status);
                 feedback.set("Sweeping TBA Funds...");
                 
                 // 1. Get TBA Balance
-                let params = serde_json::json!([tba, "latest"]);
-                let bal_res = crate::rpc::eth_call(Network::BaseSepolia, "eth_getBalance", params).await;
-                let balance = match bal_res {
-                    Ok(v) => {
-                       let hex = v["result"].as_str().unwrap_or("0");
-                       u128::from_str_radix(hex.trim_start_matches("0x"), 16).unwrap_or(0)
-                    },
-                    Err(_) => 0
+                let balance = match crate::rpc::get_balance(net, &tba).await {
+                    Ok(b) => b,
+                    Err(e) => { feedback.set(&format!("Balance Error: {}", e)); return; }
                 };
                 
-                if balance == 0 {
+                if balance.is_zero() {
                     feedback.set("TBA has no funds.");
                     return;
                 }
                 
-                let deployer_addr: ethers_core::types::Address = "0x769c18faa2e2e833a262c2ff9f6e1a9e99e52c58".parse().unwrap();
+                let deployer_addr: ethers_core::types::Address = deployer.parse().unwrap();
                 let send_amount = balance; 
                 
                 use ethers_core::abi::{encode, Token};
@@ -609,7 +1448,7 @@ pub fn WalletView() -> impl IntoView {
                 
                 let inner_data = encode(&[
                     Token::Address(deployer_addr),
-                    Token::Uint(U256::from(send_amount)),
+                    Token::Uint(send_amount.raw()),
                     Token::Bytes(vec![]), 
                     Token::Uint(U256::from(0u8)) 
                 ]);
@@ -618,40 +1457,30 @@ pub fn WalletView() -> impl IntoView {
                 let mut tx_data = execute_selector;
                 tx_data.extend(inner_data);
                 
-                 use ethers_core::types::{TransactionRequest};
-                 use ethers_signers::{LocalWallet, Signer};
+                 use ethers_core::types::{Eip1559TransactionRequest};
+                 use ethers_signers::Signer;
                  
-                 let pk = k.private_key.trim_start_matches("0x");
-                 let wallet: LocalWallet = pk.parse().unwrap();
-                 let wallet = wallet.with_chain_id(84532u64);
+                 let wallet = session.wallet().with_chain_id(net.chain_id());
                  
                  let tba_addr: ethers_core::types::Address = tba.parse().unwrap();
                  
-                 let gas_price = crate::rpc::get_gas_price(Network::BaseSepolia).await.unwrap_or(0);
-                 let effective_gas_price = gas_price + (gas_price / 10);
                  let gas_limit = 200000u64; 
 
-                 let tx = TransactionRequest::new()
+                 let tx = Eip1559TransactionRequest::new()
                     .to(tba_addr)
                     .value(0) 
                     .data(tx_data)
-                    .gas(U256::from(gas_limit))
-                    .gas_price(U256::from(effective_gas_price));
+                    .gas(U256::from(gas_limit));
                     
-                 let lat = crate::transactions::send_with_feedback(&wallet, tx, feedback, "TBA Funds Swept!").await;
+                 let lat = crate::transactions::send_with_feedback(&wallet, net, tx, TxKind::Sweep, feedback, "TBA Funds Swept!").await;
                  set_last_latency.set(lat);
                  set_refresh_trigger.update(|v| *v += 1);
              });
         }
     };
 
-    // Backup Logic
-    let download_backup = move |_| {
-        let k = keystore.get();
-        if k.private_key.is_empty() { return; }
-        
-        let json = serde_json::to_string_pretty(&k).unwrap();
-        // Create Blob and trigger download using web-sys
+    // Create Blob and trigger download using web-sys
+    fn download_json(filename: &str, json: String) {
         let window = web_sys::window().unwrap();
         let document = window.document().unwrap();
         let anchor = document.create_element("a").unwrap();
@@ -667,13 +1496,255 @@ pub fn WalletView() -> impl IntoView {
         let url = web_sys::Url::create_object_url_with_blob(&blob).unwrap();
         
         anchor_html.set_href(&url);
-        anchor_html.set_download("diamond-wallet-backup.json");
+        anchor_html.set_download(filename);
         anchor_html.click();
         
         web_sys::Url::revoke_object_url(&url).unwrap();
+    }
+
+    // Backup Logic
+    let download_backup = move |_| {
+        let k = keystore.get();
+        if k.is_watch_only() {
+            set_status.set("Watch-only wallets have no key to back up".to_string());
+            return;
+        }
+        if k.crypto.is_none() {
+            set_status.set("Encrypt the key before backing up".to_string());
+            return;
+        }
+        
+        let json = serde_json::to_string_pretty(&k).unwrap();
+        download_json("diamond-wallet-backup.json", json);
         set_status.set("Backup Downloaded".to_string());
     };
 
+    // Export the active signer as a standard V3 keystore (geth / cast wallet)
+    let export_v3 = move |_| {
+        let k = keystore.get_untracked();
+        if k.is_watch_only() {
+            set_status.set("Watch-only wallets have no key to export".to_string());
+            return;
+        }
+        let Some(crypto) = k.crypto.clone() else {
+            set_status.set("Encrypt the key before exporting".to_string());
+            return;
+        };
+
+        spawn_local(async move {
+            // Raw keys are already a V3 crypto section; phrases must be re-encrypted
+            // as the active signer's private key
+            let crypto = if k.source == KeySource::Random {
+                crypto
+            } else {
+                let Some(session) = require_unlocked() else { return; };
+                let pass = passphrase.get_untracked();
+                if pass.len() < 8 {
+                    set_status.set("Enter a keystore password (min 8 chars)".to_string());
+                    return;
+                }
+                set_status.set("Encrypting keystore...".to_string());
+                gloo_timers::future::TimeoutFuture::new(0).await;
+                match crate::crypto::encrypt(session.key_bytes(), &pass) {
+                    Ok(c) => c,
+                    Err(e) => { set_status.set(format!("Encrypt Error: {}", e)); return; }
+                }
+            };
+
+            let v3 = crate::crypto::V3Keystore::new(&k.address, crypto);
+            let json = serde_json::to_string_pretty(&v3).unwrap();
+            let ts = String::from(js_sys::Date::new_0().to_iso_string()).replace(':', "-");
+            download_json(&format!("UTC--{}--{}", ts, k.address.trim_start_matches("0x").to_lowercase()), json);
+            set_passphrase.set("".to_string());
+            set_show_v3_export.set(false);
+            set_status.set("V3 Keystore Exported".to_string());
+        });
+    };
+
+    // Decrypt a pending V3 import with its password and add it to the vault
+    let import_v3 = move |_| {
+        let Some(v3) = pending_v3.get_untracked() else { return; };
+        let pass = passphrase.get_untracked();
+
+        spawn_local(async move {
+            set_status.set("Decrypting keystore...".to_string());
+            gloo_timers::future::TimeoutFuture::new(0).await;
+
+            use ethers_signers::{LocalWallet, Signer};
+
+            let pk_bytes = match crate::crypto::decrypt(&v3.crypto, &pass) {
+                Ok(b) => b,
+                Err(e) => { set_status.set(format!("Import Failed: {}", e)); return; }
+            };
+            let wallet = match LocalWallet::from_bytes(&pk_bytes) {
+                Ok(w) => w,
+                Err(e) => { set_status.set(format!("Import Failed: invalid key ({})", e)); return; }
+            };
+            let address = format!("{:?}", wallet.address());
+            if let Some(file_addr) = &v3.address {
+                if !address.trim_start_matches("0x").eq_ignore_ascii_case(file_addr.trim_start_matches("0x")) {
+                    set_status.set("Import Failed: key does not match keystore address".to_string());
+                    return;
+                }
+            }
+
+            save_keystore(Keystore {
+                schema: KEYSTORE_SCHEMA,
+                address,
+                smart_account: None,
+                crypto: Some(v3.crypto),
+                source: KeySource::Random,
+                accounts: Vec::new(),
+                active_account: 0,
+            });
+            set_session.set(Some(Session::new(&wallet, None)));
+            set_pending_v3.set(None);
+            set_passphrase.set("".to_string());
+            set_status.set("V3 Keystore Imported".to_string());
+        });
+    };
+
+    // Split the active secret into N-of-M Shamir shares
+    let split_backup = move |_| {
+        let Some(session) = require_unlocked() else { return; };
+        let k = keystore.get_untracked();
+
+        // Phrase wallets share the phrase so every HD account can be recovered
+        let (secret, kind, address) = match &k.source {
+            KeySource::Mnemonic { .. } => {
+                let Some(phrase) = session.phrase() else {
+                    set_status.set("Unlock the recovery phrase first".to_string());
+                    return;
+                };
+                let address = k.accounts.first().map(|a| a.address.clone()).unwrap_or(k.address.clone());
+                (phrase.as_bytes().to_vec(), SecretKind::Mnemonic, address)
+            },
+            _ => (session.key_bytes().to_vec(), SecretKind::PrivateKey, k.address.clone()),
+        };
+        let secret = zeroize::Zeroizing::new(secret);
+
+        match crate::shamir::split(&secret, kind, &address, split_threshold.get_untracked(), split_total.get_untracked()) {
+            Ok(shares) => {
+                set_status.set(format!("Created {} shares. Store each one separately.", shares.len()));
+                set_split_shares.set(shares);
+            },
+            Err(e) => set_status.set(format!("Split Error: {}", e)),
+        }
+    };
+
+    let download_share = move |share: &Share| {
+        let filename = format!("diamond-share-{}-{}-of-{}.json", share.group, share.index, share.total);
+        download_json(&filename, share.to_text());
+        set_status.set(format!("Share {} Downloaded", share.index));
+    };
+
+    let add_recovery_share = move |text: &str| {
+        match Share::parse(text) {
+            Ok(share) => set_recovery_shares.update(|list| {
+                if !list.iter().any(|s| s.group == share.group && s.index == share.index) {
+                    list.push(share);
+                }
+            }),
+            Err(e) => set_status.set(format!("Invalid Share: {}", e)),
+        }
+    };
+
+    // Shares may be pasted one per line
+    let add_pasted_shares = move |_| {
+        let input = share_input.get_untracked();
+        for line in input.lines().filter(|l| !l.trim().is_empty()) {
+            add_recovery_share(line);
+        }
+        set_share_input.set("".to_string());
+    };
+
+    // Rebuild and encrypt a keystore from a threshold set of shares
+    let recover_from_shares = move |_| {
+        let pass = passphrase.get_untracked();
+        if pass.len() < 8 {
+            set_status.set("Passphrase must be at least 8 characters".to_string());
+            return;
+        }
+        let shares = recovery_shares.get_untracked();
+        let secret = match crate::shamir::combine(&shares) {
+            Ok(s) => s,
+            Err(e) => { set_status.set(format!("Recovery Failed: {}", e)); return; }
+        };
+        let expected = shares[0].address.clone();
+
+        use ethers_signers::{LocalWallet, Signer};
+
+        match shares[0].kind {
+            SecretKind::Mnemonic => {
+                let phrase = match String::from_utf8(secret.to_vec()).map_err(|e| e.to_string()).and_then(|p| crate::mnemonic::normalize(&p)) {
+                    Ok(p) => p,
+                    Err(e) => { set_status.set(format!("Recovery Failed: {}", e)); return; }
+                };
+                let derived = crate::mnemonic::derive_wallet(&phrase, &crate::mnemonic::account_path(0));
+                if !derived.map(|w| format!("{:?}", w.address()).eq_ignore_ascii_case(&expected)).unwrap_or(false) {
+                    set_status.set("Recovery Failed: shares do not match their address".to_string());
+                    return;
+                }
+                save_mnemonic_wallet(phrase, pass);
+            },
+            SecretKind::PrivateKey => {
+                let wallet = match LocalWallet::from_bytes(&secret) {
+                    Ok(w) => w,
+                    Err(e) => { set_status.set(format!("Recovery Failed: invalid key ({})", e)); return; }
+                };
+                let address = format!("{:?}", wallet.address());
+                if !address.eq_ignore_ascii_case(&expected) {
+                    set_status.set("Recovery Failed: shares do not match their address".to_string());
+                    return;
+                }
+                spawn_local(async move {
+                    set_status.set("Encrypting key...".to_string());
+                    gloo_timers::future::TimeoutFuture::new(0).await;
+                    let crypto = match crate::crypto::encrypt(&secret, &pass) {
+                        Ok(c) => c,
+                        Err(e) => { set_status.set(format!("Encrypt Error: {}", e)); return; }
+                    };
+                    save_keystore(Keystore {
+                        schema: KEYSTORE_SCHEMA,
+                        address,
+                        smart_account: None,
+                        crypto: Some(crypto),
+                        source: KeySource::Random,
+                        accounts: Vec::new(),
+                        active_account: 0,
+                    });
+                    set_session.set(Some(Session::new(&wallet, None)));
+                    set_passphrase.set("".to_string());
+                    set_status.set("Wallet Recovered".to_string());
+                });
+            },
+        }
+        set_recovery_shares.set(Vec::new());
+        set_show_share_recovery.set(false);
+    };
+
+    let share_input_ref = create_node_ref::<leptos::html::Input>();
+
+    let on_share_files = move |ev: leptos::ev::Event| {
+        use wasm_bindgen::JsCast;
+        let input = ev.target().unwrap().unchecked_into::<web_sys::HtmlInputElement>();
+        let Some(files) = input.files() else { return; };
+        for i in 0..files.length() {
+            let Some(file) = files.get(i) else { continue; };
+            let reader = web_sys::FileReader::new().unwrap();
+            let reader_c = reader.clone();
+            let onload = wasm_bindgen::closure::Closure::wrap(Box::new(move |_e: web_sys::Event| {
+                if let Some(text) = reader_c.result().ok().and_then(|r| r.as_string()) {
+                    add_recovery_share(&text);
+                }
+            }) as Box<dyn FnMut(_)>);
+            reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+            onload.forget();
+            reader.read_as_text(&file).unwrap();
+        }
+        input.set_value("");
+    };
+
     let import_input_ref = create_node_ref::<leptos::html::Input>();
     
     let import_opt_click = move |_| {
@@ -692,15 +1763,37 @@ pub fn WalletView() -> impl IntoView {
                  let onload = wasm_bindgen::closure::Closure::wrap(Box::new(move |_e: web_sys::Event| {
                      if let Ok(res) = reader_c.result() {
                          if let Some(text) = res.as_string() {
-                             match serde_json::from_str::<Keystore>(&text) {
-                                 Ok(k) => {
-                                     let _ = LocalStorage::set("diamond_wallet_keystore", &k);
-                                     set_keystore.set(k);
+                             // Standard V3 files carry a uuid `id`; ours never do
+                             let is_v3 = serde_json::from_str::<serde_json::Value>(&text)
+                                 .map(|v| v.get("id").is_some())
+                                 .unwrap_or(false);
+                             if is_v3 {
+                                 match crate::crypto::V3Keystore::parse(&text) {
+                                     Ok(v3) => {
+                                         set_pending_v3.set(Some(v3));
+                                         set_status.set("V3 Keystore loaded. Enter its password.".to_string());
+                                     },
+                                     Err(e) => set_status.set(format!("Import Failed: {}", e)),
+                                 }
+                                 return;
+                             }
+                             match parse_keystore(&text) {
+                                 Ok((k, legacy_key)) => {
+                                     // Encrypted imports stay locked until the passphrase is entered;
+                                     // plaintext backups are held in memory until encrypted
+                                     set_session.set(legacy_key.as_deref().and_then(|pk| Session::from_hex(pk).ok()));
+                                     if legacy_key.is_some() {
+                                         set_status.set("Unencrypted backup. Set a passphrase.".to_string());
+                                         set_keystore.set(k);
+                                     } else {
+                                         set_status.set("Backup Imported. Enter passphrase.".to_string());
+                                         save_keystore(k);
+                                     }
                                      // Reset signals
-                                     set_bal_sepolia.set("...".to_string());
+                                     set_signer_balance.set(None);
  
                                  },
-                                 Err(e) => logging::log!("Parse Error: {}", e),
+                                 Err(e) => set_status.set(format!("Import Failed: {}", e)),
                              }
                          }
                      }
@@ -718,9 +1811,36 @@ pub fn WalletView() -> impl IntoView {
             // Sticky Header
             <header class="app-header">
                 <div class="header-status">
+                    {move || chain_mismatch.get().map(|msg| view! {
+                        <div style="color:#ff4444; font-size:12px; font-weight:bold;">{format!("⚠ Wrong chain: {}. Signing disabled.", msg)}</div>
+                    })}
                     <div style="color:#4CAF50; font-size:12px; font-weight:bold;">{move || status.get()}</div>
+                    <div class="tiny-text" style="color:#888;">
+                        {move || match (head.get(), ws_live.get()) {
+                            (Some(n), true) => format!("● live · block {}", n),
+                            (Some(n), false) => format!("○ polling · block {}", n),
+                            (None, _) => "○ connecting".to_string(),
+                        }}
+                    </div>
+                    <select style="font-size:10px; margin-top:2px;"
+                        on:change=move |ev| {
+                            let name = event_target_value(&ev);
+                            if let Some(net) = Network::ALL.into_iter().find(|n| n.name() == name) {
+                                switch_network(net);
+                            }
+                        }>
+                        {Network::ALL.into_iter().map(|net| view! {
+                            <option value=net.name() selected=move || settings.get().network == net>{net.name()}</option>
+                        }).collect_view()}
+                    </select>
                 </div>
                 <div class="header-icons">
+                    <leptos_router::A href="/history" class="wallet-btn">
+                        <svg class="wallet-icon" fill="none" stroke="currentColor" viewBox="0 0 24 24" xmlns="http://www.w3.org/2000/svg">
+                            <circle cx="12" cy="12" r="9" stroke-width="2"></circle>
+                            <polyline points="12 7 12 12 15 14" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"></polyline>
+                        </svg>
+                    </leptos_router::A>
                      <button class="wallet-btn" on:click=move |_| set_show_brain_menu.set(true)>
                         <svg class="wallet-icon" fill="none" stroke="currentColor" viewBox="0 0 24 24" xmlns="http://www.w3.org/2000/svg">
                             <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M9.5 2A2.5 2.5 0 0 1 12 4.5v15a2.5 2.5 0 0 1-4.96.44 2.5 2.5 0 0 1-2.96-3.08 3 3 0 0 1-.34-5.58 2.5 2.5 0 0 1 1.32-4.24 2.5 2.5 0 0 1 1.98-3A2.5 2.5 0 0 1 9.5 2Z"></path>
@@ -743,6 +1863,41 @@ pub fn WalletView() -> impl IntoView {
                     <div class="modal-overlay" on:click=move |_| set_show_wallet_menu.set(false)>
                         <div class="modal-content" on:click=move |ev| ev.stop_propagation()>
                             <h3 class="modal-title">"Device Wallet"</h3>
+
+                            // Vault Wallet List
+                            <div class="wallet-list" style="margin-bottom:10px;">
+                                {move || {
+                                    let v = vault.get();
+                                    v.wallets.iter().enumerate().map(|(i, entry)| {
+                                        let active = v.active == Some(i);
+                                        let name = if entry.keystore.is_watch_only() {
+                                            format!("{} (watch)", entry.name)
+                                        } else {
+                                            entry.name.clone()
+                                        };
+                                        view! {
+                                            <div class="flex-row" style="align-items:center; gap:5px;">
+                                                <button class=if active { "text-btn active" } else { "text-btn" }
+                                                    style="flex:1; text-align:left;"
+                                                    on:click=move |_| switch_wallet(i)>
+                                                    {name}
+                                                </button>
+                                                {move || if confirm_delete.get() == Some(i) {
+                                                    view! {
+                                                        <button class="danger-btn" on:click=move |_| delete_wallet(i)>"Delete"</button>
+                                                        <button class="cancel-btn" on:click=move |_| set_confirm_delete.set(None)>"Keep"</button>
+                                                    }.into_view()
+                                                } else {
+                                                    view! {
+                                                        <button class="danger-btn-outline" title="Remove from vault" on:click=move |_| set_confirm_delete.set(Some(i))>"x"</button>
+                                                    }.into_view()
+                                                }}
+                                            </div>
+                                        }
+                                    }).collect_view()
+                                }}
+                                <button class="text-btn" style="width:100%; border:1px solid #333;" on:click=add_wallet>"+ Add Wallet"</button>
+                            </div>
                             
                              {move || if !keystore.get().address.is_empty() {
                                 view! {
@@ -755,21 +1910,56 @@ pub fn WalletView() -> impl IntoView {
                                                 </svg>
                                             </button>
                                         </div>
+                                        {settings.get().network.address_url(&keystore.get().address).map(|url| view! {
+                                            <a class="tiny-text" href=url target="_blank" rel="noopener" style="display:block; text-align:center;">"View on Explorer"</a>
+                                        })}
                                         <div class="balance-grid">
                                             <div class="bal-item">
-                                                <span class="label">"Sepolia"</span>
-                                                <span class="val">{bal_sepolia.get()}</span>
+                                                <span class="label">{move || settings.get().network.name()}</span>
+                                                <span class="val">{move || balance_text(signer_balance.get())}</span>
                                             </div>
+                                            {move || settings.get().verify_balances.then(|| proof_mark_view(signer_proof.get()))}
                                         </div>
                                     </div>
                                 }.into_view()
                              } else {
                                 view! { <p>"No Wallet Created"</p> }.into_view()
                              }}
+
+                            {move || if watch_only() {
+                                view! { <p class="tiny-text" style="color:#888; text-align:center;">"Watch-only: this wallet has no key, so sending, sweeping and minting are disabled."</p> }.into_view()
+                            } else {
+                                view! { }.into_view()
+                            }}
+
+                            // HD Account Switcher
+                            {move || if !keystore.get().accounts.is_empty() {
+                                let k = keystore.get();
+                                view! {
+                                    <div class="account-list" style="margin-bottom:10px;">
+                                        {k.accounts.iter().enumerate().map(|(i, acc)| {
+                                            let active = i == k.active_account;
+                                            let addr = acc.address.clone();
+                                            let bal = balance_text(account_bals.get().get(&addr).cloned());
+                                            view! {
+                                                <button class=if active { "text-btn active" } else { "text-btn" }
+                                                    style="width:100%; display:flex; justify-content:space-between; font-family:monospace; font-size:10px;"
+                                                    on:click=move |_| switch_account(i)>
+                                                    <span>{format!("#{} {}...{}", i, &addr[..6], &addr[addr.len() - 4..])}</span>
+                                                    <span>{bal}</span>
+                                                </button>
+                                            }
+                                        }).collect_view()}
+                                        <button class="text-btn" style="width:100%; border:1px solid #333;" on:click=add_account>"+ Add Account"</button>
+                                    </div>
+                                }.into_view()
+                            } else {
+                                view! { }.into_view()
+                            }}
                             
                             // Send ETH Button (Toggle)
                             {move || if !show_device_send.get() && !keystore.get().address.is_empty() {
-                                view! { <button class="primary-btn" on:click=move |_| set_show_device_send.set(true)>"Send ETH"</button> }.into_view()
+                                view! { <button class="primary-btn" disabled=watch_only on:click=move |_| set_show_device_send.set(true)>"Send ETH"</button> }.into_view()
                             } else if show_device_send.get() {
                                 view! {
                                     <div class="sponsor-box">
@@ -794,8 +1984,10 @@ pub fn WalletView() -> impl IntoView {
 
                             <hr style="border-color:#333; width:100%"/>
                             
-                            // Sponsor
-                            {move || if show_sponsor_modal.get() {
+                            // Sponsor (testnets only)
+                            {move || if !settings.get().network.is_testnet() {
+                                view! { }.into_view()
+                            } else if show_sponsor_modal.get() {
                                 view! {
                                     <div class="sponsor-box">
                                         <p>"Enter PIN:"</p>
@@ -816,8 +2008,147 @@ pub fn WalletView() -> impl IntoView {
                                 }.into_view()
                             }}
 
-                            <button class="primary-btn" on:click=sweep_funds>"Sweep Signer -> Deployer"</button>
-                            <button class="primary-btn" on:click=download_backup>"Download Backup"</button>
+                            <button class="primary-btn" disabled=watch_only on:click=sweep_funds>"Sweep Signer -> Deployer"</button>
+                            <button class="primary-btn" disabled=watch_only on:click=download_backup>"Download Backup"</button>
+                            {move || if show_v3_export.get() {
+                                view! {
+                                    <div class="sponsor-box">
+                                        <p>"Keystore Password"</p>
+                                        <input type="password" placeholder="Password for the exported file"
+                                            on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+                                            prop:value=passphrase
+                                            style="margin-bottom:5px;" />
+                                        <div class="flex-row">
+                                            <button class="primary-btn" on:click=export_v3>"Export"</button>
+                                            <button class="cancel-btn" on:click=move |_| set_show_v3_export.set(false)>"Cancel"</button>
+                                        </div>
+                                    </div>
+                                }.into_view()
+                            } else if keystore.get().source == KeySource::Random {
+                                // Raw keys export their existing encryption as-is
+                                view! { <button class="primary-btn" disabled=watch_only on:click=export_v3>"Export V3 Keystore"</button> }.into_view()
+                            } else {
+                                view! { <button class="primary-btn" disabled=watch_only on:click=move |_| set_show_v3_export.set(true)>"Export V3 Keystore"</button> }.into_view()
+                            }}
+
+                            {move || if !split_shares.get().is_empty() {
+                                view! {
+                                    <div class="sponsor-box">
+                                        <p class="tiny-text">"Store each share in a different place. Any threshold of them restores the wallet."</p>
+                                        {split_shares.get().into_iter().map(|share| {
+                                            let label = format!("Share {} of {} (need {})", share.index, share.total, share.threshold);
+                                            let text = share.to_text();
+                                            view! {
+                                                <div class="flex-row" style="align-items:center; gap:5px; margin-bottom:5px;">
+                                                    <span style="flex:1;">{label}</span>
+                                                    <button class="text-btn" on:click=move |_| download_share(&share)>"Download"</button>
+                                                    <button class="text-btn" on:click=move |_| { copy_to_clipboard(text.clone()); set_status.set("Share Copied".to_string()); }>"Copy"</button>
+                                                </div>
+                                            }
+                                        }).collect_view()}
+                                        <button class="cancel-btn" on:click=move |_| { set_split_shares.set(Vec::new()); set_show_split.set(false); }>"Done"</button>
+                                    </div>
+                                }.into_view()
+                            } else if show_split.get() {
+                                view! {
+                                    <div class="sponsor-box">
+                                        <p>"Split Backup"</p>
+                                        <div class="flex-row" style="align-items:center; gap:5px; margin-bottom:5px;">
+                                            <input type="number" min="2" max="16"
+                                                on:input=move |ev| set_split_threshold.set(event_target_value(&ev).parse().unwrap_or(2))
+                                                prop:value=move || split_threshold.get().to_string() />
+                                            <span>"of"</span>
+                                            <input type="number" min="2" max="16"
+                                                on:input=move |ev| set_split_total.set(event_target_value(&ev).parse().unwrap_or(3))
+                                                prop:value=move || split_total.get().to_string() />
+                                        </div>
+                                        <div class="flex-row">
+                                            <button class="primary-btn" on:click=split_backup>"Create Shares"</button>
+                                            <button class="cancel-btn" on:click=move |_| set_show_split.set(false)>"Cancel"</button>
+                                        </div>
+                                    </div>
+                                }.into_view()
+                            } else {
+                                view! { <button class="primary-btn" disabled=watch_only on:click=move |_| set_show_split.set(true)>"Split Backup (Shamir)"</button> }.into_view()
+                            }}
+
+                            <p style="margin:5px 0 2px;">"RPC Endpoints"</p>
+                            {move || {
+                                let now = js_sys::Date::now();
+                                crate::provider::snapshot(settings.get().network).into_iter().map(|(url, h)| {
+                                    let state = if h.cooling_down(now) {
+                                        "cooling down".to_string()
+                                    } else {
+                                        h.latency_ms.map(|ms| format!("{:.0}ms", ms)).unwrap_or("unused".to_string())
+                                    };
+                                    view! {
+                                        <p class="tiny-text" style="font-family:monospace; margin:0;">
+                                            {format!("{} · {} · {} ok / {} failed", url.trim_start_matches("https://"), state, h.successes, h.failures)}
+                                        </p>
+                                    }
+                                }).collect_view()
+                            }}
+                            <select on:change=move |ev| set_rpc_quorum(event_target_value(&ev).parse().unwrap_or(1)) style="margin:5px 0;">
+                                <option value="1" selected=move || settings.get().rpc_quorum <= 1>"Single endpoint reads"</option>
+                                <option value="2" selected=move || settings.get().rpc_quorum >= 2>"Quorum: 2 endpoints agree on nonce/balance"</option>
+                            </select>
+                            <label class="tiny-text" style="display:flex; align-items:center; gap:5px;">
+                                <input type="checkbox" prop:checked=move || settings.get().verify_balances
+                                    on:change=move |ev| set_verify_balances(event_target_checked(&ev))/>
+                                "Verify balances with Merkle proofs"
+                            </label>
+                            <label class="tiny-text" style="display:flex; align-items:center; gap:5px;">
+                                <input type="checkbox" prop:checked=move || settings.with(|s| s.legacy_networks.contains(&s.network))
+                                    on:change=move |ev| set_legacy_transactions(event_target_checked(&ev))/>
+                                {move || format!("Sign legacy (type 0) transactions on {}", settings.get().network.name())}
+                            </label>
+                            <p class="tiny-text" style="font-family:monospace; margin:0;">
+                                {move || {
+                                    // Re-read whenever a new block arrives
+                                    head.track();
+                                    let s = crate::cache::stats();
+                                    let total = (s.hits + s.misses).max(1);
+                                    format!("Cache: {} hits / {} misses ({:.0}%) · {} shared · {} entries",
+                                        s.hits, s.misses, s.hits as f64 * 100.0 / total as f64, s.joined, s.entries)
+                                }}
+                            </p>
+                            <label class="tiny-text" style="display:flex; align-items:center; gap:5px;">
+                                <input type="checkbox" prop:checked=move || settings.get().persist_rpc_cache
+                                    on:change=move |ev| set_persist_cache(event_target_checked(&ev))/>
+                                "Keep cache across reloads"
+                            </label>
+                            <button class="cancel-btn" on:click=clear_rpc_cache style="margin:5px 0;">"Clear RPC Cache"</button>
+                            <p style="margin:5px 0 2px;">"Transaction Speed"</p>
+                            <select on:change=move |ev| {
+                                let speed = crate::fees::Speed::ALL.into_iter().find(|s| s.name() == event_target_value(&ev));
+                                if let Some(speed) = speed { set_fee_speed(speed); }
+                            } style="margin-bottom:5px;">
+                                {move || crate::fees::Speed::ALL.into_iter().map(|speed| {
+                                    let label = match fee_estimate.get() {
+                                        Some(fees) => {
+                                            let s = fees.get(speed);
+                                            format!("{} · max {} gwei (tip {})", speed.name(), gwei_text(s.max_fee_per_gas), gwei_text(s.max_priority_fee_per_gas))
+                                        },
+                                        None => speed.name().to_string(),
+                                    };
+                                    view! {
+                                        <option value=speed.name() selected=move || settings.get().fee_speed == speed>{label}</option>
+                                    }
+                                }).collect_view()}
+                            </select>
+                            {move || fee_estimate.get().map(|fees| view! {
+                                <p class="tiny-text" style="margin:0 0 5px;">{format!("Base fee: {} gwei", gwei_text(fees.base_fee))}</p>
+                            })}
+                            <p style="margin:5px 0 2px;">"Auto-Lock"</p>
+                            <select on:change=move |ev| set_auto_lock(event_target_value(&ev).parse().unwrap_or(5)) style="margin-bottom:5px;">
+                                {[(1u32, "After 1 minute"), (5, "After 5 minutes"), (15, "After 15 minutes"), (60, "After 1 hour"), (0, "Never")]
+                                    .into_iter()
+                                    .map(|(m, label)| view! {
+                                        <option value=m.to_string() selected=move || settings.get().auto_lock_minutes == m>{label}</option>
+                                    })
+                                    .collect_view()}
+                            </select>
+                            <button class="primary-btn" disabled=move || session.get().is_none() on:click=move |_| lock_wallet("Wallet Locked")>"Lock Now"</button>
                             
                             <hr style="border-color:#333; width:100%"/>
 
@@ -825,9 +2156,15 @@ pub fn WalletView() -> impl IntoView {
                                 view! {
                                     <div class="warning-box">
                                         <p>"DANGEROUS: Wipe Key?"</p>
+                                        <p class="tiny-text">"Every account is drained first. The wallet is only removed once all are empty, unless you have a backup."</p>
+                                        <label class="tiny-text" style="display:flex; align-items:center; gap:5px;">
+                                            <input type="checkbox" prop:checked=move || backup_confirmed.get()
+                                                on:change=move |ev| set_backup_confirmed.set(event_target_checked(&ev))/>
+                                            "I have a backup of this wallet's phrase or key"
+                                        </label>
                                         <div class="flex-row">
                                             <button class="danger-btn" on:click=clear_wallet>"CONFIRM"</button>
-                                            <button class="cancel-btn" on:click=move |_| set_show_clear_confirm.set(false)>"Cancel"</button>
+                                            <button class="cancel-btn" on:click=move |_| { set_show_clear_confirm.set(false); set_backup_confirmed.set(false); }>"Cancel"</button>
                                         </div>
                                     </div>
                                 }.into_view()
@@ -851,6 +2188,12 @@ pub fn WalletView() -> impl IntoView {
                     <div class="modal-overlay" on:click=move |_| set_show_brain_menu.set(false)>
                         <div class="modal-content" on:click=move |ev| ev.stop_propagation()>
                             <h3 class="modal-title">"Smart Account"</h3>
+
+                            {move || if watch_only() {
+                                view! { <p class="tiny-text" style="color:#888; text-align:center;">"Watch-only: balances are tracked but the TBA cannot be controlled from here."</p> }.into_view()
+                            } else {
+                                view! { }.into_view()
+                            }}
                             
                              {move || if let Some(sa) = keystore.get().smart_account {
                                 view! {
@@ -866,15 +2209,16 @@ pub fn WalletView() -> impl IntoView {
                                         </div>
                                          <div class="balance-grid">
                                             <div class="bal-item">
-                                                <span class="label">"Sepolia"</span>
-                                                <span class="val">{tba_bal_sepolia.get()}</span>
+                                                <span class="label">{move || settings.get().network.name()}</span>
+                                                <span class="val">{move || balance_text(tba_balance.get())}</span>
                                             </div>
+                                            {move || settings.get().verify_balances.then(|| proof_mark_view(tba_proof.get()))}
                                         </div>
                                     </div>
                                     
                                     // Send ETH (Smart Account)
                                      {move || if !show_sa_send.get() {
-                                        view! { <button class="primary-btn" style="width:100%" on:click=move |_| set_show_sa_send.set(true)>"Send ETH"</button> }.into_view()
+                                        view! { <button class="primary-btn" style="width:100%" disabled=watch_only on:click=move |_| set_show_sa_send.set(true)>"Send ETH"</button> }.into_view()
                                     } else {
                                         view! {
                                             <div class="sponsor-box">
@@ -897,7 +2241,7 @@ pub fn WalletView() -> impl IntoView {
                                     
                                     <div class="flex-col" style="gap:10px; margin-top:10px;">
                                         <button class="sponsor-btn" on:click=request_tba_sponsor>"Request Sponsor (TBA)"</button>
-                                        <button class="primary-btn" on:click=sweep_tba_funds>"Sweep TBA -> Deployer"</button>
+                                        <button class="primary-btn" disabled=watch_only on:click=sweep_tba_funds>"Sweep TBA -> Deployer"</button>
                                     </div>
                                 }.into_view()
                              } else {
@@ -905,7 +2249,7 @@ pub fn WalletView() -> impl IntoView {
                                     <div>
                                         <p style="text-align:center; color:#888;">"Not Deployed"</p>
                                         <div class="flex-col" style="gap:10px; margin-top:10px;">
-                                            <button class="primary-btn" on:click=mint_identity>"1. Mint Identity NFT"</button>
+                                            <button class="primary-btn" disabled=watch_only on:click=mint_identity>"1. Mint Identity NFT"</button>
                                             <button class="text-btn" style="border:1px solid #333;" on:click=compute_tba>"2. Compute TBA Address"</button>
                                         </div>
                                     </div>
@@ -923,12 +2267,154 @@ pub fn WalletView() -> impl IntoView {
             // Scrollable Content
             <div class="app-content">
                 // Home Screen (Empty for now, waiting for Apps)
-                {move || if keystore.get().address.is_empty() {
+                {move || if keystore.get().address.is_empty() && pending_mnemonic.get().is_some() {
+                    let phrase = pending_mnemonic.get().unwrap_or_default();
+                    let indices = confirm_indices.get();
+                    if indices.is_empty() {
+                        view! {
+                            <div class="onboarding">
+                                <p class="tiny-text">"Write these words down in order. They are the only way to recover this wallet."</p>
+                                <ol class="mnemonic-grid" style="font-family:monospace; columns:2; margin:0 0 10px 0;">
+                                    {phrase.split_whitespace().map(|w| view! { <li>{w.to_string()}</li> }).collect_view()}
+                                </ol>
+                                <button class="primary-btn" on:click=start_confirm>"I've Written Them Down"</button>
+                                <button class="cancel-btn" on:click=move |_| set_pending_mnemonic.set(None)>"Cancel"</button>
+                            </div>
+                        }.into_view()
+                    } else {
+                        let prompt = indices.iter().map(|i| format!("#{}", i + 1)).collect::<Vec<_>>().join(", ");
+                        view! {
+                            <div class="onboarding">
+                                <p class="tiny-text">{format!("Enter words {} separated by spaces", prompt)}</p>
+                                <input type="text" placeholder="word word word"
+                                    on:input=move |ev| set_confirm_input.set(event_target_value(&ev))
+                                    prop:value=confirm_input
+                                    style="margin-bottom:5px;" />
+                                <button class="primary-btn" on:click=confirm_mnemonic>"Confirm"</button>
+                                <button class="cancel-btn" on:click=move |_| set_confirm_indices.set(Vec::new())>"Show Words Again"</button>
+                            </div>
+                        }.into_view()
+                    }
+                } else if keystore.get().address.is_empty() && pending_v3.get().is_some() {
+                    view! {
+                        <div class="onboarding">
+                            <p class="tiny-text">"Enter the password this keystore was encrypted with. It will also unlock the wallet here."</p>
+                            <input type="password" placeholder="Keystore Password"
+                                on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+                                prop:value=passphrase
+                                style="margin-bottom:5px;" />
+                            <button class="primary-btn" on:click=import_v3>"Import Keystore"</button>
+                            <button class="cancel-btn" on:click=move |_| set_pending_v3.set(None)>"Cancel"</button>
+                        </div>
+                    }.into_view()
+                } else if keystore.get().address.is_empty() && show_watch_form.get() {
+                    view! {
+                        <div class="onboarding">
+                            <input type="text" placeholder="Label (optional)"
+                                on:input=move |ev| set_new_wallet_name.set(event_target_value(&ev))
+                                prop:value=new_wallet_name
+                                style="margin-bottom:5px;" />
+                            <input type="text" placeholder="Address (0x...)"
+                                on:input=move |ev| set_watch_address.set(event_target_value(&ev))
+                                prop:value=watch_address
+                                style="margin-bottom:5px;" />
+                            <input type="text" placeholder="Smart Account / TBA (optional)"
+                                on:input=move |ev| set_watch_tba.set(event_target_value(&ev))
+                                prop:value=watch_tba
+                                style="margin-bottom:5px;" />
+                            <button class="primary-btn" on:click=add_watch_only>"Watch Address"</button>
+                            <button class="cancel-btn" on:click=move |_| set_show_watch_form.set(false)>"Cancel"</button>
+                        </div>
+                    }.into_view()
+                } else if keystore.get().address.is_empty() && show_share_recovery.get() {
+                    let shares = recovery_shares.get();
+                    let progress = match shares.first() {
+                        Some(s) => format!("{} of {} shares loaded", shares.len(), s.threshold),
+                        None => "Load or paste your shares".to_string(),
+                    };
+                    view! {
+                        <div class="onboarding">
+                            <p class="tiny-text">{progress}</p>
+                            <textarea placeholder="Paste shares, one per line" rows="3"
+                                on:input=move |ev| set_share_input.set(event_target_value(&ev))
+                                prop:value=share_input
+                                style="margin-bottom:5px;"></textarea>
+                            <div class="flex-row">
+                                <button class="text-btn" on:click=add_pasted_shares>"Add Pasted"</button>
+                                <button class="text-btn" on:click=move |_| { if let Some(i) = share_input_ref.get() { i.click(); } }>"Load Files"</button>
+                            </div>
+                            <input type="file" multiple node_ref=share_input_ref style="display:none" on:change=on_share_files accept=".json" />
+                            <input type="password" placeholder="New Passphrase (min 8 chars)"
+                                on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+                                prop:value=passphrase
+                                style="margin-bottom:5px;" />
+                            <button class="primary-btn" on:click=recover_from_shares>"Recover Wallet"</button>
+                            <button class="cancel-btn" on:click=move |_| { set_recovery_shares.set(Vec::new()); set_show_share_recovery.set(false); }>"Cancel"</button>
+                        </div>
+                    }.into_view()
+                } else if keystore.get().address.is_empty() && show_mnemonic_import.get() {
+                    view! {
+                        <div class="onboarding">
+                            <textarea placeholder="12 or 24 word recovery phrase" rows="3"
+                                on:input=move |ev| set_mnemonic_input.set(event_target_value(&ev))
+                                prop:value=mnemonic_input
+                                style="margin-bottom:5px;"></textarea>
+                            <input type="password" placeholder="New Passphrase (min 8 chars)"
+                                on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+                                prop:value=passphrase
+                                style="margin-bottom:5px;" />
+                            <button class="primary-btn" on:click=import_mnemonic>"Recover Wallet"</button>
+                            <button class="cancel-btn" on:click=move |_| set_show_mnemonic_import.set(false)>"Cancel"</button>
+                        </div>
+                    }.into_view()
+                } else if keystore.get().address.is_empty() {
                      view! { 
                         <div class="onboarding">
+                            <input type="text" placeholder="Wallet name (optional)"
+                                on:input=move |ev| set_new_wallet_name.set(event_target_value(&ev))
+                                prop:value=new_wallet_name
+                                style="margin-bottom:5px;" />
+                            <input type="password" placeholder="Passphrase (min 8 chars)"
+                                on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+                                prop:value=passphrase
+                                style="margin-bottom:5px;" />
+                            <select on:change=move |ev| set_word_count.set(event_target_value(&ev).parse().unwrap_or(12)) style="margin-bottom:5px;">
+                                <option value="12" selected=move || word_count.get() == 12>"12 words"</option>
+                                <option value="24" selected=move || word_count.get() == 24>"24 words"</option>
+                            </select>
                             <button class="primary-btn" on:click=generate_wallet>"Create New Wallet"</button> 
-                            <button class="text-btn" on:click=import_opt_click>"Import Backup JSON"</button>
+                            <button class="text-btn" on:click=move |_| set_show_mnemonic_import.set(true)>"Import Recovery Phrase"</button>
+                            <button class="text-btn" on:click=move |_| set_show_share_recovery.set(true)>"Recover From Shares"</button>
+                            <button class="text-btn" on:click=import_opt_click>"Import Backup / V3 Keystore"</button>
+                            <button class="text-btn" on:click=move |_| set_show_watch_form.set(true)>"Watch Address Only"</button>
                             <input type="file" node_ref=import_input_ref style="display:none" on:change=on_file_change accept=".json" />
+                            {move || if !vault.get().wallets.is_empty() {
+                                view! { <button class="cancel-btn" on:click=move |_| switch_wallet(0)>"Back to Wallets"</button> }.into_view()
+                            } else {
+                                view! { }.into_view()
+                            }}
+                        </div>
+                    }.into_view()
+                } else if keystore.get().crypto.is_none() && !watch_only() {
+                    view! {
+                        <div class="onboarding">
+                            <p class="tiny-text">"This key is stored unencrypted. Choose a passphrase to encrypt it."</p>
+                            <input type="password" placeholder="New Passphrase (min 8 chars)"
+                                on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+                                prop:value=passphrase
+                                style="margin-bottom:5px;" />
+                            <button class="primary-btn" on:click=encrypt_existing>"Encrypt Key"</button>
+                        </div>
+                    }.into_view()
+                } else if session.get().is_none() && !watch_only() {
+                    view! {
+                        <div class="onboarding">
+                            <p class="tiny-text" style="font-family:monospace; word-break:break-all;">{keystore.get().address}</p>
+                            <input type="password" placeholder="Passphrase"
+                                on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+                                prop:value=passphrase
+                                style="margin-bottom:5px;" />
+                            <button class="primary-btn" on:click=unlock_wallet>"Unlock"</button>
                         </div>
                     }.into_view()
                 } else {